@@ -2,10 +2,15 @@
 set -e
 
 # Build the contract
-cargo build --release --bin mixocracy
+# The PolkaVM target and build-std flags are passed here rather than in
+# .cargo/config.toml so that `cargo test` still runs on the host
+cargo build --release --bin mixocracy \
+    --target riscv64emac-unknown-none-polkavm.json \
+    -Z build-std=core,alloc \
+    -Z build-std-features=panic_immediate_abort
 
 # Link it
 polkatool link --strip --output mixocracy.polkavm \
     target/riscv64emac-unknown-none-polkavm/release/mixocracy
 
-echo "Contract built successfully: mixocracy.polkavm"
\ No newline at end of file
+echo "Contract built successfully: mixocracy.polkavm"
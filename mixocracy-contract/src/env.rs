@@ -0,0 +1,221 @@
+// Host environment abstraction
+//
+// The contract logic only talks to the chain through `Env`. On PolkaVM this is
+// `HostEnv`, a thin wrapper around the pallet-revive host functions; under
+// `cargo test` it is `MockEnv`, an in-memory backend with a settable caller and clock.
+
+pub trait Env {
+    // Copies the value stored under `key` into `output` (truncating if it doesn't fit)
+    // and returns the number of bytes written, or `None` if the key is unset
+    fn get_storage(&self, key: &[u8; 32], output: &mut [u8]) -> Option<usize>;
+    fn set_storage(&mut self, key: &[u8; 32], value: &[u8]);
+    fn caller(&self) -> [u8; 20];
+    // Block timestamp as reported by the host
+    fn now(&self) -> u64;
+    fn hash_keccak_256(&self, input: &[u8]) -> [u8; 32];
+    fn deposit_event(&mut self, topics: &[[u8; 32]], data: &[u8]);
+    // Code hash of the running contract
+    fn own_code_hash(&self) -> [u8; 32];
+    // Replaces the contract's code, keeping its storage; takes effect from the next call
+    fn set_code_hash(&mut self, code_hash: &[u8; 32]);
+    // Read-only call into another contract; copies its return data into `output` like
+    // `get_storage` and returns the length, or `None` if the call failed or reverted
+    fn call_contract(&self, callee: &[u8; 20], input: &[u8], output: &mut [u8]) -> Option<usize>;
+}
+
+#[cfg(not(test))]
+pub use host::HostEnv;
+
+#[cfg(not(test))]
+mod host {
+    use super::Env;
+    use uapi::{CallFlags, HostFn, HostFnImpl as api, StorageFlags};
+
+    pub struct HostEnv;
+
+    impl Env for HostEnv {
+        fn get_storage(&self, key: &[u8; 32], output: &mut [u8]) -> Option<usize> {
+            let mut output = output;
+            match api::get_storage(StorageFlags::empty(), key, &mut output) {
+                Ok(_) => Some(output.len()),
+                Err(_) => None,
+            }
+        }
+
+        fn set_storage(&mut self, key: &[u8; 32], value: &[u8]) {
+            api::set_storage(StorageFlags::empty(), key, value);
+        }
+
+        fn caller(&self) -> [u8; 20] {
+            let mut caller = [0u8; 20];
+            api::caller(&mut caller);
+            caller
+        }
+
+        fn now(&self) -> u64 {
+            let mut timestamp_bytes = [0u8; 32];
+            api::now(&mut timestamp_bytes);
+            let mut timestamp_u64_bytes = [0u8; 8];
+            timestamp_u64_bytes.copy_from_slice(&timestamp_bytes[0..8]);
+            u64::from_le_bytes(timestamp_u64_bytes)
+        }
+
+        fn hash_keccak_256(&self, input: &[u8]) -> [u8; 32] {
+            let mut hash = [0u8; 32];
+            api::hash_keccak_256(input, &mut hash);
+            hash
+        }
+
+        fn deposit_event(&mut self, topics: &[[u8; 32]], data: &[u8]) {
+            api::deposit_event(topics, data);
+        }
+
+        fn own_code_hash(&self) -> [u8; 32] {
+            let mut code_hash = [0u8; 32];
+            api::own_code_hash(&mut code_hash);
+            code_hash
+        }
+
+        fn set_code_hash(&mut self, code_hash: &[u8; 32]) {
+            api::set_code_hash(code_hash);
+        }
+
+        fn call_contract(&self, callee: &[u8; 20], input: &[u8], output: &mut [u8]) -> Option<usize> {
+            let mut output = output;
+            // No weight, proof size or deposit limit beyond what the caller has left
+            match api::call(
+                CallFlags::READ_ONLY,
+                callee,
+                u64::MAX,
+                u64::MAX,
+                &[u8::MAX; 32],
+                &[0u8; 32],
+                input,
+                Some(&mut output),
+            ) {
+                Ok(_) => Some(output.len()),
+                Err(_) => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub use mock::MockEnv;
+
+#[cfg(test)]
+mod mock {
+    use super::Env;
+    use alloc::collections::BTreeMap;
+    use alloc::vec::Vec;
+    use ethabi::{decode, encode, ParamType, Token, Uint};
+    use sha3::{Digest, Keccak256};
+
+    // Stand-in for a token contract: answers ERC-20 / ERC-721 `balanceOf(address)` from
+    // `balances` and ERC-1155 `balanceOf(address,uint256)` from `id_balances`, and
+    // reverts on anything else
+    #[derive(Default)]
+    pub struct MockToken {
+        pub balances: BTreeMap<[u8; 20], Uint>,
+        pub id_balances: BTreeMap<([u8; 20], Uint), Uint>,
+    }
+
+    impl MockToken {
+        fn call(&self, input: &[u8]) -> Option<Vec<u8>> {
+            let (selector, params) = input.split_at_checked(4)?;
+            let balance = match selector {
+                [0x70, 0xa0, 0x82, 0x31] => {
+                    let decoded = decode(&[ParamType::Address], params).ok()?;
+                    let owner = decoded[0].clone().into_address()?;
+                    self.balances.get(&owner.0)
+                },
+                [0x00, 0xfd, 0xd5, 0x8e] => {
+                    let decoded = decode(&[ParamType::Address, ParamType::Uint(256)], params).ok()?;
+                    let owner = decoded[0].clone().into_address()?;
+                    let id = decoded[1].clone().into_uint()?;
+                    self.id_balances.get(&(owner.0, id))
+                },
+                _ => return None,
+            };
+            Some(encode(&[Token::Uint(balance.copied().unwrap_or_default())]))
+        }
+    }
+
+    #[derive(Default)]
+    pub struct MockEnv {
+        pub storage: BTreeMap<[u8; 32], Vec<u8>>,
+        pub caller: [u8; 20],
+        pub now: u64,
+        pub events: Vec<(Vec<[u8; 32]>, Vec<u8>)>,
+        pub code_hash: [u8; 32],
+        pub tokens: BTreeMap<[u8; 20], MockToken>,
+    }
+
+    impl MockEnv {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn set_caller(&mut self, caller: [u8; 20]) {
+            self.caller = caller;
+        }
+
+        pub fn set_now(&mut self, now: u64) {
+            self.now = now;
+        }
+
+        // Token contract at `address`, deployed on first use
+        pub fn token(&mut self, address: [u8; 20]) -> &mut MockToken {
+            self.tokens.entry(address).or_default()
+        }
+
+        // Events whose first topic matches the given signature topic
+        pub fn events_with_topic(&self, signature: [u8; 32]) -> Vec<&(Vec<[u8; 32]>, Vec<u8>)> {
+            self.events.iter().filter(|(topics, _)| topics.first() == Some(&signature)).collect()
+        }
+    }
+
+    impl Env for MockEnv {
+        fn get_storage(&self, key: &[u8; 32], output: &mut [u8]) -> Option<usize> {
+            let value = self.storage.get(key)?;
+            let len = value.len().min(output.len());
+            output[..len].copy_from_slice(&value[..len]);
+            Some(len)
+        }
+
+        fn set_storage(&mut self, key: &[u8; 32], value: &[u8]) {
+            self.storage.insert(*key, value.to_vec());
+        }
+
+        fn caller(&self) -> [u8; 20] {
+            self.caller
+        }
+
+        fn now(&self) -> u64 {
+            self.now
+        }
+
+        fn hash_keccak_256(&self, input: &[u8]) -> [u8; 32] {
+            Keccak256::digest(input).into()
+        }
+
+        fn deposit_event(&mut self, topics: &[[u8; 32]], data: &[u8]) {
+            self.events.push((topics.to_vec(), data.to_vec()));
+        }
+
+        fn own_code_hash(&self) -> [u8; 32] {
+            self.code_hash
+        }
+
+        fn set_code_hash(&mut self, code_hash: &[u8; 32]) {
+            self.code_hash = *code_hash;
+        }
+
+        fn call_contract(&self, callee: &[u8; 20], input: &[u8], output: &mut [u8]) -> Option<usize> {
+            let value = self.tokens.get(callee)?.call(input)?;
+            let len = value.len().min(output.len());
+            output[..len].copy_from_slice(&value[..len]);
+            Some(len)
+        }
+    }
+}
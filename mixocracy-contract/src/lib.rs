@@ -1,12 +1,23 @@
-#![no_main]
-#![no_std]
+#![cfg_attr(not(test), no_main)]
+#![cfg_attr(not(test), no_std)]
 extern crate alloc;
 use alloc::{string::String, vec, vec::Vec};
+use core::cmp::Ordering;
 use ethabi::{encode, decode, Token, ParamType};
+#[cfg(not(test))]
 use polkavm_derive::polkavm_export;
+#[cfg(not(test))]
 use simplealloc::SimpleAlloc;
-use uapi::{HostFn, HostFnImpl as api, StorageFlags, ReturnFlags};
+#[cfg(not(test))]
+use uapi::{HostFn, HostFnImpl as api, ReturnFlags};
 
+mod env;
+#[cfg(test)]
+mod tests;
+
+use env::Env;
+
+#[cfg(not(test))]
 #[global_allocator]
 static ALLOCATOR: SimpleAlloc<51200> = SimpleAlloc::new(); // 50KB allocator
 
@@ -26,6 +37,109 @@ const PREFIX_ALL_DJS: u8 = 11;
 const PREFIX_ALL_DJ_COUNT: u8 = 12;
 const PREFIX_SONG_REMOVED: u8 = 13;
 const PREFIX_SET_END_TIME: u8 = 14;
+const PREFIX_CURRENT_SET: u8 = 15;
+const PREFIX_SONG_PLAYED_AT: u8 = 16;
+const PREFIX_PLAY_HISTORY: u8 = 17;
+const PREFIX_PLAY_COUNT: u8 = 18;
+const PREFIX_DELEGATE_PERMISSIONS: u8 = 19;
+const PREFIX_DELEGATES: u8 = 20;
+const PREFIX_DELEGATE_COUNT: u8 = 21;
+const PREFIX_UNIVERSAL_REMOVER_RETIRED: u8 = 22;
+const PREFIX_PENDING_OWNER: u8 = 23;
+const PREFIX_ROLE_MEMBER: u8 = 24;
+const PREFIX_ROLE_MEMBERS: u8 = 25;
+const PREFIX_ROLE_MEMBER_COUNT: u8 = 26;
+const PREFIX_SCHEMA_VERSION: u8 = 27;
+const PREFIX_MIGRATION_CURSOR: u8 = 28;
+const PREFIX_PREVIOUS_CODE_HASH: u8 = 29;
+const PREFIX_SONG_TITLE: u8 = 30;
+const PREFIX_SONG_ARTIST: u8 = 31;
+const PREFIX_SONG_SOURCE_URI: u8 = 32;
+const PREFIX_SONG_DURATION: u8 = 33;
+const PREFIX_SONG_ADDED_BY: u8 = 34;
+const PREFIX_SONG_ADDED_AT: u8 = 35;
+const PREFIX_SONG_SUGGESTED: u8 = 36;
+const PREFIX_SUGGESTER_SONGS: u8 = 37;
+const PREFIX_SUGGESTER_SONG_COUNT: u8 = 38;
+const PREFIX_SUGGESTION_APPROVAL: u8 = 39;
+const PREFIX_SONG_PENDING: u8 = 40;
+const PREFIX_SUGGESTION_LIMITS: u8 = 41;
+const PREFIX_SONG_INDEX: u8 = 42;
+const PREFIX_REJECT_DUPLICATES: u8 = 43;
+const PREFIX_SONG_VOTERS: u8 = 44;
+const PREFIX_SONG_VOTER_COUNT: u8 = 45;
+const PREFIX_SONG_VOTER_LISTED: u8 = 46;
+const PREFIX_SONG_MERGED_INTO: u8 = 47;
+const PREFIX_VOTE_BUDGET: u8 = 48;
+const PREFIX_VOTES_SPENT: u8 = 49;
+const PREFIX_DOWNVOTES: u8 = 50;
+const PREFIX_HAS_DOWNVOTED: u8 = 51;
+const PREFIX_AUTO_HIDE: u8 = 52;
+const PREFIX_QUADRATIC_VOTING: u8 = 53;
+const PREFIX_VOTES_CAST: u8 = 54;
+const PREFIX_CREDITS_SPENT: u8 = 55;
+const PREFIX_VOTE_DECAY: u8 = 56;
+const PREFIX_VOTED_AT: u8 = 57;
+const PREFIX_VOTES_CLEARED_AT: u8 = 58;
+const PREFIX_VOTE_TOKEN: u8 = 59;
+const PREFIX_GATE: u8 = 60;
+const PREFIX_DOWNVOTE_WEIGHT: u8 = 61;
+const PREFIX_SET_QUEUE_START: u8 = 62;
+
+// Delegate permission bits
+const PERMISSION_MARK_PLAYED: u32 = 1 << 0;
+const PERMISSION_REMOVE_SONG: u32 = 1 << 1;
+const PERMISSION_APPROVE_SUGGESTION: u32 = 1 << 2;
+const PERMISSION_START_STOP_SET: u32 = 1 << 3;
+const ALL_PERMISSIONS: u32 = PERMISSION_MARK_PLAYED | PERMISSION_REMOVE_SONG | PERMISSION_APPROVE_SUGGESTION | PERMISSION_START_STOP_SET;
+
+// Storage layout version: 1 is the original headerless string layout,
+// 2 stores strings with the versioned length prefix
+const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+// Length limits for variable-length values
+const MAX_SONG_NAME_LEN: usize = 256;
+const MAX_METADATA_LEN: usize = 512;
+const MAX_SONG_TITLE_LEN: usize = 128;
+// Leaves room for the " - " separator so "Artist - Title" fits in MAX_SONG_NAME_LEN
+const MAX_SONG_ARTIST_LEN: usize = MAX_SONG_NAME_LEN - MAX_SONG_TITLE_LEN - 3;
+const MAX_SOURCE_URI_LEN: usize = 256;
+
+// Strings are stored as [STRING_MARKER, version, u16 LE length, bytes...]. The marker
+// can never start valid UTF-8, so it tells these apart from the original headerless values
+const STRING_MARKER: u8 = 0xff;
+const STRING_ENCODING_V1: u8 = 1;
+const STRING_HEADER_LEN: usize = 4;
+
+// Largest page a paged view returns, so a response always fits in the heap
+const MAX_PAGE_SIZE: u32 = 100;
+
+// Ranking scores are fixed point, with SCORE_SCALE standing for one fresh vote
+const SCORE_SCALE: u64 = 1_000_000;
+// 2^(-i/16) in SCORE_SCALE units, for decaying vote weights within one half-life
+const HALF_LIFE_STEPS: [u64; 17] = [
+    1_000_000, 957_603, 917_004, 878_126, 840_896, 805_245, 771_105, 738_413, 707_107,
+    677_128, 648_420, 620_929, 594_604, 569_394, 545_254, 522_137, 500_000,
+];
+
+// How a voter's token balance turns into vote weight
+const WEIGHT_CURVE_LINEAR: u32 = 0;
+const WEIGHT_CURVE_SQRT: u32 = 1;
+const WEIGHT_CURVE_CAPPED: u32 = 2;
+
+// Token functions the contract calls on other contracts; ERC-721 shares the ERC-20 signature
+const ERC20_BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31]; // balanceOf(address)
+const ERC1155_BALANCE_OF: [u8; 4] = [0x00, 0xfd, 0xd5, 0x8e]; // balanceOf(address,uint256)
+
+// Ticket contracts a DJ can gate voting and suggestions on
+const GATE_NONE: u32 = 0;
+const GATE_ERC721: u32 = 1;
+const GATE_ERC1155: u32 = 2;
+
+// Role identifiers
+const ROLE_ADMIN: [u8; 32] = [0xdf, 0x8b, 0x4c, 0x52, 0x0f, 0xfe, 0x19, 0x7c, 0x53, 0x43, 0xc6, 0xf5, 0xae, 0xc5, 0x95, 0x70, 0x15, 0x1e, 0xf9, 0xa4, 0x92, 0xf2, 0xc6, 0x24, 0xfd, 0x45, 0xdd, 0xde, 0x61, 0x35, 0xec, 0x42]; // keccak256("ADMIN")
+const ROLE_DJ_MANAGER: [u8; 32] = [0x57, 0xb5, 0x50, 0x0e, 0xf3, 0xe2, 0x0f, 0x76, 0x75, 0x35, 0x13, 0x26, 0xb9, 0xd4, 0xc1, 0xf4, 0xe9, 0x58, 0x10, 0x46, 0xd5, 0xd7, 0xd5, 0x4d, 0x8e, 0x31, 0xb1, 0x32, 0xd9, 0xbc, 0x03, 0x9f]; // keccak256("DJ_MANAGER")
+const ROLE_MODERATOR: [u8; 32] = [0x58, 0xc8, 0xe1, 0x1d, 0xea, 0xb7, 0x91, 0x0e, 0x89, 0xbf, 0x18, 0xa1, 0x16, 0x8c, 0x6e, 0x6e, 0xf2, 0x87, 0x48, 0xf0, 0x0f, 0xd3, 0x09, 0x45, 0x49, 0x45, 0x9f, 0x01, 0xce, 0xc5, 0xe0, 0xaa]; // keccak256("MODERATOR")
 
 // Function selectors computed from ethers.js keccak256
 const SELECTOR_REGISTER_DJ: [u8; 4] = [0x19, 0xc2, 0x36, 0xc0]; // registerDj(address)
@@ -53,6 +167,267 @@ const SELECTOR_GET_ALL_SONGS_WITH_VOTES: [u8; 4] = [0x47, 0xf8, 0xdc, 0x84]; //
 const SELECTOR_GET_DJ_INFO_EXTENDED: [u8; 4] = [0x6c, 0x44, 0xd3, 0x19]; // getDjInfoExtended(address)
 const SELECTOR_UNVOTE: [u8; 4] = [0x02, 0xaa, 0x9b, 0xe2]; // unvote(address,uint256)
 const SELECTOR_IS_SONG_REMOVED: [u8; 4] = [0x59, 0xd2, 0x38, 0x66]; // isSongRemoved(address,uint256)
+const SELECTOR_GET_CURRENT_SET_ID: [u8; 4] = [0x7c, 0x08, 0x6c, 0x0e]; // getCurrentSetId(address)
+const SELECTOR_GET_SET_INFO: [u8; 4] = [0x8a, 0xcf, 0xc2, 0xb5]; // getSetInfo(address,uint256)
+const SELECTOR_GET_SET_SONGS_WITH_VOTES: [u8; 4] = [0xb4, 0x37, 0x68, 0xc3]; // getAllSongsWithVotes(address,uint256)
+const SELECTOR_GET_SET_SONG_COUNT: [u8; 4] = [0xc9, 0x20, 0xa1, 0x4e]; // getSongCount(address,uint256)
+const SELECTOR_HAS_VOTED_IN_SET: [u8; 4] = [0x60, 0x32, 0xd9, 0xab]; // hasVoted(address,address,uint256,uint256)
+const SELECTOR_GET_TOP_SONGS: [u8; 4] = [0xe7, 0xb9, 0x6e, 0x73]; // getTopSongs(address,uint256)
+const SELECTOR_GET_QUEUE_POSITION: [u8; 4] = [0xe4, 0xa7, 0x24, 0x0e]; // getQueuePosition(address,uint256)
+const SELECTOR_ADVANCE: [u8; 4] = [0x16, 0xe9, 0xdf, 0x10]; // advance(address)
+const SELECTOR_MARK_PLAYED: [u8; 4] = [0xee, 0xd7, 0x92, 0xb8]; // markPlayed(address,uint256)
+const SELECTOR_NOW_PLAYING: [u8; 4] = [0x36, 0x0c, 0x79, 0xcd]; // nowPlaying(address)
+const SELECTOR_GET_PLAY_HISTORY: [u8; 4] = [0xff, 0x87, 0xf2, 0x9c]; // getPlayHistory(address,uint256,uint256)
+const SELECTOR_GET_PLAYED_AT: [u8; 4] = [0x2e, 0x25, 0x44, 0x42]; // getPlayedAt(address,uint256)
+const SELECTOR_ADD_DELEGATE: [u8; 4] = [0x5b, 0xcf, 0xd2, 0x12]; // addDelegate(address,uint256)
+const SELECTOR_REMOVE_DELEGATE: [u8; 4] = [0x67, 0xe7, 0x64, 0x6f]; // removeDelegate(address)
+const SELECTOR_GET_DELEGATES: [u8; 4] = [0x16, 0x9d, 0xb7, 0x7d]; // getDelegates(address)
+const SELECTOR_REMOVE_SONG_FOR: [u8; 4] = [0xbf, 0x3e, 0x11, 0x07]; // removeSongFor(address,uint256)
+const SELECTOR_RETIRE_UNIVERSAL_REMOVER: [u8; 4] = [0x2f, 0x87, 0xd3, 0xb1]; // retireUniversalRemover()
+const SELECTOR_IS_UNIVERSAL_REMOVER_RETIRED: [u8; 4] = [0x14, 0x34, 0x0f, 0x6f]; // isUniversalRemoverRetired()
+const SELECTOR_OWNER: [u8; 4] = [0x8d, 0xa5, 0xcb, 0x5b]; // owner()
+const SELECTOR_PENDING_OWNER: [u8; 4] = [0xe3, 0x0c, 0x39, 0x78]; // pendingOwner()
+const SELECTOR_TRANSFER_OWNERSHIP: [u8; 4] = [0xf2, 0xfd, 0xe3, 0x8b]; // transferOwnership(address)
+const SELECTOR_ACCEPT_OWNERSHIP: [u8; 4] = [0x79, 0xba, 0x50, 0x97]; // acceptOwnership()
+const SELECTOR_RENOUNCE_OWNERSHIP: [u8; 4] = [0x71, 0x50, 0x18, 0xa6]; // renounceOwnership()
+const SELECTOR_GRANT_ROLE: [u8; 4] = [0x2f, 0x2f, 0xf1, 0x5d]; // grantRole(bytes32,address)
+const SELECTOR_REVOKE_ROLE: [u8; 4] = [0xd5, 0x47, 0x74, 0x1f]; // revokeRole(bytes32,address)
+const SELECTOR_HAS_ROLE: [u8; 4] = [0x91, 0xd1, 0x48, 0x54]; // hasRole(bytes32,address)
+const SELECTOR_GET_ROLE_MEMBERS: [u8; 4] = [0xa3, 0x24, 0x6a, 0xd3]; // getRoleMembers(bytes32)
+const SELECTOR_GET_ALL_DJS_PAGED: [u8; 4] = [0x14, 0xd6, 0x36, 0x03]; // getAllDjsPaged(uint256,uint256)
+const SELECTOR_GET_ACTIVE_DJS_PAGED: [u8; 4] = [0xa0, 0x07, 0x30, 0x5f]; // getActiveDjsPaged(uint256,uint256)
+const SELECTOR_GET_SONGS_WITH_VOTES_PAGED: [u8; 4] = [0x2a, 0x77, 0xa4, 0x0e]; // getSongsWithVotesPaged(address,uint256,uint256,uint256)
+const SELECTOR_GET_SONGS_WITH_VOTES_FROM: [u8; 4] = [0x95, 0x30, 0x77, 0xd8]; // getSongsWithVotesFrom(address,uint256,uint256,uint256)
+const SELECTOR_SCHEMA_VERSION: [u8; 4] = [0x4e, 0x2c, 0xe6, 0xd3]; // schemaVersion()
+const SELECTOR_MIGRATE: [u8; 4] = [0x45, 0x4b, 0x06, 0x08]; // migrate(uint256)
+const SELECTOR_UPGRADE_TO: [u8; 4] = [0xbe, 0x5c, 0x62, 0xb2]; // upgradeTo(bytes32)
+const SELECTOR_UPGRADE_TO_FORCED: [u8; 4] = [0xc5, 0xe0, 0x3c, 0xfd]; // upgradeTo(bytes32,bool)
+const SELECTOR_CODE_HASH: [u8; 4] = [0x18, 0xed, 0xaa, 0xf2]; // codeHash()
+const SELECTOR_PREVIOUS_CODE_HASH: [u8; 4] = [0xbd, 0x5b, 0x76, 0xb4]; // previousCodeHash()
+const SELECTOR_ADD_SONG_DETAILED: [u8; 4] = [0x2c, 0x9f, 0xa5, 0xbc]; // addSongDetailed(string,string,string,uint256)
+const SELECTOR_SUGGEST_SONG_DETAILED: [u8; 4] = [0xc6, 0xec, 0x40, 0xb9]; // suggestSongDetailed(address,string,string,string,uint256)
+const SELECTOR_GET_SONG_DETAILS: [u8; 4] = [0x04, 0x65, 0x73, 0x92]; // getSongDetails(address,uint256)
+const SELECTOR_GET_SONG_ORIGIN: [u8; 4] = [0x5a, 0xb3, 0x8a, 0xe0]; // getSongOrigin(address,uint256)
+const SELECTOR_GET_SONGS_BY_SUGGESTER: [u8; 4] = [0x31, 0xb8, 0x51, 0xf1]; // getSongsBySuggester(address,address)
+const SELECTOR_SET_SUGGESTION_APPROVAL: [u8; 4] = [0x68, 0x9c, 0xf6, 0x4c]; // setSuggestionApproval(bool)
+const SELECTOR_REQUIRES_SUGGESTION_APPROVAL: [u8; 4] = [0x77, 0x3e, 0xdc, 0x40]; // requiresSuggestionApproval(address)
+const SELECTOR_APPROVE_SUGGESTION: [u8; 4] = [0x31, 0x1e, 0x15, 0x3e]; // approveSuggestion(address,uint256)
+const SELECTOR_REJECT_SUGGESTION: [u8; 4] = [0xec, 0xae, 0xf5, 0x05]; // rejectSuggestion(address,uint256)
+const SELECTOR_GET_PENDING_SUGGESTIONS: [u8; 4] = [0x97, 0xbc, 0x43, 0x1c]; // getPendingSuggestions(address)
+const SELECTOR_SET_SUGGESTION_LIMITS: [u8; 4] = [0x80, 0x47, 0x20, 0x7f]; // setSuggestionLimits(uint256,uint256)
+const SELECTOR_GET_SUGGESTION_LIMITS: [u8; 4] = [0xf9, 0x29, 0x56, 0xcc]; // getSuggestionLimits(address)
+const SELECTOR_GET_SUGGESTION_ALLOWANCE: [u8; 4] = [0xe1, 0x12, 0xc0, 0x46]; // getSuggestionAllowance(address,address)
+const SELECTOR_SET_REJECT_DUPLICATES: [u8; 4] = [0x1a, 0xe0, 0x6b, 0x5a]; // setRejectDuplicates(bool)
+const SELECTOR_REJECTS_DUPLICATES: [u8; 4] = [0x11, 0xb8, 0x5e, 0x5c]; // rejectsDuplicates(address)
+const SELECTOR_FIND_SONG: [u8; 4] = [0xc8, 0x81, 0x4d, 0x9c]; // findSong(address,string,string)
+const SELECTOR_MERGE_SONGS: [u8; 4] = [0x4c, 0x69, 0xa1, 0xf3]; // mergeSongs(address,uint256,uint256)
+const SELECTOR_GET_MERGED_INTO: [u8; 4] = [0xf3, 0x3d, 0x5a, 0x9d]; // getMergedInto(address,uint256)
+const SELECTOR_SET_VOTE_BUDGET: [u8; 4] = [0xfb, 0xe5, 0xbd, 0x42]; // setVoteBudget(uint256)
+const SELECTOR_GET_VOTE_BUDGET: [u8; 4] = [0xfe, 0x17, 0xcc, 0xba]; // getVoteBudget(address)
+const SELECTOR_GET_REMAINING_VOTES: [u8; 4] = [0x8d, 0x18, 0xe3, 0x79]; // getRemainingVotes(address,address)
+const SELECTOR_DOWNVOTE: [u8; 4] = [0xcc, 0x85, 0x8b, 0x2c]; // downvote(address,uint256)
+const SELECTOR_UNDOWNVOTE: [u8; 4] = [0x8f, 0x35, 0x34, 0xde]; // undownvote(address,uint256)
+const SELECTOR_HAS_DOWNVOTED: [u8; 4] = [0xbc, 0x92, 0x96, 0x3e]; // hasDownvoted(address,address,uint256)
+const SELECTOR_GET_SONGS_WITH_SCORES: [u8; 4] = [0xde, 0x7b, 0x0f, 0x76]; // getSongsWithScores(address)
+const SELECTOR_SET_AUTO_HIDE: [u8; 4] = [0xbf, 0x00, 0xe9, 0xbe]; // setAutoHide(bool,int256)
+const SELECTOR_GET_AUTO_HIDE: [u8; 4] = [0xfb, 0x60, 0xe3, 0x61]; // getAutoHide(address)
+const SELECTOR_SET_QUADRATIC_VOTING: [u8; 4] = [0x75, 0xeb, 0x51, 0xb6]; // setQuadraticVoting(bool,uint256)
+const SELECTOR_GET_QUADRATIC_VOTING: [u8; 4] = [0x25, 0x89, 0x4c, 0xfe]; // getQuadraticVoting(address)
+const SELECTOR_CAST_VOTES: [u8; 4] = [0x3d, 0xa0, 0xcb, 0x70]; // castVotes(address,uint256,uint256)
+const SELECTOR_WITHDRAW_VOTES: [u8; 4] = [0x4a, 0x56, 0xf4, 0x52]; // withdrawVotes(address,uint256,uint256)
+const SELECTOR_GET_REMAINING_CREDITS: [u8; 4] = [0x21, 0x11, 0xed, 0xbd]; // getRemainingCredits(address,address)
+const SELECTOR_GET_VOTES_CAST: [u8; 4] = [0x9c, 0x46, 0xa2, 0xbb]; // getVotesCast(address,address,uint256)
+const SELECTOR_SET_VOTE_DECAY: [u8; 4] = [0xca, 0xed, 0x3d, 0x95]; // setVoteDecay(uint256)
+const SELECTOR_GET_VOTE_DECAY: [u8; 4] = [0xd7, 0x3f, 0x20, 0xbb]; // getVoteDecay(address)
+const SELECTOR_GET_TOP_SONGS_WITH_SCORES: [u8; 4] = [0x7c, 0xdf, 0x23, 0x63]; // getTopSongsWithScores(address,uint256)
+const SELECTOR_SET_VOTE_TOKEN: [u8; 4] = [0x7d, 0x68, 0xa3, 0xe6]; // setVoteToken(address,uint8,uint256,uint256)
+const SELECTOR_GET_VOTE_TOKEN: [u8; 4] = [0x38, 0x4c, 0x87, 0x7e]; // getVoteToken(address)
+const SELECTOR_GET_VOTE_WEIGHT: [u8; 4] = [0x2a, 0x33, 0x18, 0x73]; // getVoteWeight(address,address)
+const SELECTOR_SET_GATE: [u8; 4] = [0xba, 0xd1, 0x49, 0xf2]; // setGate(uint8,address,uint256)
+const SELECTOR_GET_GATE: [u8; 4] = [0x1b, 0xbc, 0xd2, 0xc0]; // getGate(address)
+
+// Event topics (keccak256 of the event signature)
+const EVENT_DJ_REGISTERED: [u8; 32] = [0xc2, 0xe3, 0xba, 0x72, 0x7c, 0x2b, 0x97, 0x04, 0xca, 0x8b, 0xe9, 0x32, 0x0c, 0xd3, 0xbc, 0xf6, 0xe3, 0xde, 0x1d, 0xf6, 0x16, 0xcb, 0x4e, 0x1d, 0x54, 0x56, 0x69, 0x51, 0x1d, 0xd2, 0x2f, 0x45]; // DjRegistered(address)
+const EVENT_DJ_REMOVED: [u8; 32] = [0x6d, 0xce, 0x3e, 0x0e, 0x14, 0x11, 0x09, 0x89, 0xae, 0x3a, 0x74, 0x9f, 0xa3, 0xc4, 0x6c, 0x30, 0x57, 0x4c, 0xca, 0x70, 0x78, 0x8d, 0xd7, 0x82, 0x1a, 0x5e, 0xaf, 0x27, 0x10, 0x36, 0x80, 0x24]; // DjRemoved(address)
+const EVENT_SONG_ADDED: [u8; 32] = [0x08, 0x05, 0x2c, 0xc2, 0xb4, 0xbb, 0xe4, 0xad, 0xdb, 0x8b, 0x6b, 0x41, 0xb3, 0x13, 0x39, 0xf4, 0x9f, 0x0a, 0x11, 0x57, 0x33, 0xea, 0xe2, 0x07, 0x00, 0xcf, 0xfe, 0x42, 0x52, 0x34, 0xb8, 0x69]; // SongAdded(address,uint256,string)
+const EVENT_SONG_SUGGESTED: [u8; 32] = [0xbb, 0xba, 0xcd, 0xb8, 0x8e, 0x5d, 0x81, 0x51, 0xf8, 0x1f, 0x1e, 0xc5, 0x77, 0xf2, 0x08, 0xd5, 0x63, 0x29, 0xa9, 0xdb, 0x20, 0xd7, 0xf1, 0x54, 0x33, 0x5d, 0x9d, 0x56, 0x9b, 0x4c, 0x39, 0xca]; // SongSuggested(address,uint256,address,string)
+const EVENT_VOTED: [u8; 32] = [0xec, 0xf6, 0x12, 0xba, 0x46, 0x99, 0xd1, 0xad, 0xae, 0x61, 0xf2, 0x5a, 0x29, 0xd8, 0x62, 0x24, 0x89, 0x11, 0x34, 0x42, 0x6a, 0x6f, 0x3e, 0x51, 0x5b, 0xe9, 0x49, 0x47, 0xfd, 0x96, 0xe1, 0x21]; // Voted(address,uint256,address,uint256)
+const EVENT_UNVOTED: [u8; 32] = [0x88, 0x41, 0x8a, 0x89, 0xc9, 0x2d, 0x9c, 0xdd, 0x7b, 0x05, 0x80, 0x90, 0xf5, 0x67, 0x2f, 0xef, 0xb9, 0x64, 0x07, 0x24, 0xc2, 0x77, 0xcb, 0xbc, 0xd6, 0x3d, 0xa8, 0x7b, 0xfc, 0x65, 0xc8, 0xc5]; // Unvoted(address,uint256,address,uint256)
+const EVENT_SONG_REMOVED: [u8; 32] = [0x19, 0x1f, 0xb4, 0x60, 0x83, 0xf0, 0x6e, 0x51, 0xa9, 0x6f, 0xdf, 0x39, 0x1d, 0xeb, 0x9c, 0x5c, 0x37, 0x77, 0x0d, 0xd2, 0x0c, 0xa2, 0xbe, 0x5d, 0xb8, 0xb0, 0x1b, 0x7e, 0x1c, 0xb3, 0x80, 0x85]; // SongRemoved(address,uint256,address)
+const EVENT_SET_STARTED: [u8; 32] = [0x81, 0x8c, 0xe8, 0x23, 0xda, 0x24, 0x88, 0x2f, 0x8e, 0xbe, 0xcc, 0x28, 0xee, 0x88, 0x52, 0x69, 0xbd, 0x69, 0x9d, 0x57, 0x0f, 0x8d, 0x07, 0x06, 0x45, 0x6a, 0x48, 0x2c, 0xf8, 0x9f, 0xe0, 0x5f]; // SetStarted(address,uint256,uint256)
+const EVENT_SET_STOPPED: [u8; 32] = [0x43, 0xe6, 0x5b, 0xd6, 0x7b, 0x45, 0xd0, 0xf0, 0xd6, 0x3e, 0x48, 0x9e, 0x8f, 0x1d, 0x92, 0x20, 0xf9, 0xe0, 0x96, 0x23, 0x61, 0x83, 0x9f, 0xd2, 0x89, 0x6d, 0x22, 0xbc, 0x21, 0xbd, 0x22, 0x7f]; // SetStopped(address,uint256,uint256)
+const EVENT_VOTES_CLEARED: [u8; 32] = [0x17, 0xea, 0x4a, 0xb7, 0xae, 0x54, 0xe4, 0x15, 0x16, 0xe5, 0xe8, 0xde, 0x39, 0x06, 0x13, 0x0e, 0xdb, 0x71, 0x2f, 0x42, 0x0c, 0x42, 0x8a, 0x8f, 0x5a, 0x3f, 0x28, 0x8c, 0xe4, 0xcd, 0x68, 0xa1]; // VotesCleared(address,uint256)
+const EVENT_DJ_METADATA_UPDATED: [u8; 32] = [0x40, 0xd1, 0x3b, 0x15, 0x3a, 0x53, 0x7a, 0x04, 0x05, 0x86, 0xd3, 0x77, 0xda, 0x76, 0xda, 0x98, 0x11, 0xba, 0x22, 0x5a, 0x45, 0x10, 0xc6, 0x06, 0x35, 0xed, 0xfe, 0x2c, 0x26, 0xcd, 0x75, 0x01]; // DjMetadataUpdated(address,string)
+const EVENT_SONG_PLAYED: [u8; 32] = [0x44, 0xf3, 0xfe, 0x6b, 0x2d, 0xb9, 0x25, 0x1a, 0x86, 0x7d, 0xc7, 0x1f, 0xb0, 0x31, 0xb0, 0x25, 0xaa, 0x01, 0xcc, 0xa2, 0x7d, 0xcd, 0x66, 0xb8, 0x50, 0xa0, 0x4c, 0x4d, 0x29, 0x6c, 0xa0, 0x96]; // SongPlayed(address,uint256,uint256,uint256)
+const EVENT_DELEGATE_UPDATED: [u8; 32] = [0xe4, 0x62, 0x08, 0xae, 0x3c, 0x81, 0x57, 0x62, 0x1e, 0x96, 0xe0, 0x3a, 0x67, 0xb3, 0xfc, 0x7b, 0x61, 0xb7, 0x34, 0x02, 0x2b, 0x04, 0xcf, 0x09, 0x7b, 0x94, 0xdd, 0x99, 0x4d, 0xd9, 0x5b, 0x43]; // DelegateUpdated(address,address,uint256)
+const EVENT_DELEGATE_REMOVED: [u8; 32] = [0xe8, 0x51, 0x4d, 0xd4, 0xbe, 0x96, 0x84, 0x31, 0x13, 0x55, 0x80, 0xc2, 0x63, 0x14, 0xec, 0x35, 0xaf, 0xaf, 0xc8, 0x17, 0x82, 0x68, 0x60, 0x3f, 0x99, 0x62, 0x55, 0x84, 0x96, 0x0d, 0x9c, 0x16]; // DelegateRemoved(address,address)
+const EVENT_UNIVERSAL_REMOVER_RETIRED: [u8; 32] = [0x42, 0x7a, 0x10, 0x05, 0x05, 0xb1, 0x0c, 0x79, 0x19, 0xe1, 0x4d, 0x34, 0xad, 0xd6, 0x36, 0x0a, 0x10, 0xa3, 0xab, 0x2c, 0x44, 0xca, 0x43, 0x4d, 0xaf, 0x00, 0x65, 0x06, 0xb8, 0xf7, 0x22, 0x44]; // UniversalRemoverRetired()
+const EVENT_OWNERSHIP_TRANSFER_STARTED: [u8; 32] = [0x38, 0xd1, 0x6b, 0x8c, 0xac, 0x22, 0xd9, 0x9f, 0xc7, 0xc1, 0x24, 0xb9, 0xcd, 0x0d, 0xe2, 0xd3, 0xfa, 0x1f, 0xae, 0xf4, 0x20, 0xbf, 0xe7, 0x91, 0xd8, 0xc3, 0x62, 0xd7, 0x65, 0xe2, 0x27, 0x00]; // OwnershipTransferStarted(address,address)
+const EVENT_OWNERSHIP_TRANSFERRED: [u8; 32] = [0x8b, 0xe0, 0x07, 0x9c, 0x53, 0x16, 0x59, 0x14, 0x13, 0x44, 0xcd, 0x1f, 0xd0, 0xa4, 0xf2, 0x84, 0x19, 0x49, 0x7f, 0x97, 0x22, 0xa3, 0xda, 0xaf, 0xe3, 0xb4, 0x18, 0x6f, 0x6b, 0x64, 0x57, 0xe0]; // OwnershipTransferred(address,address)
+const EVENT_ROLE_GRANTED: [u8; 32] = [0x2f, 0x87, 0x88, 0x11, 0x7e, 0x7e, 0xff, 0x1d, 0x82, 0xe9, 0x26, 0xec, 0x79, 0x49, 0x01, 0xd1, 0x7c, 0x78, 0x02, 0x4a, 0x50, 0x27, 0x09, 0x40, 0x30, 0x45, 0x40, 0xa7, 0x33, 0x65, 0x6f, 0x0d]; // RoleGranted(bytes32,address,address)
+const EVENT_ROLE_REVOKED: [u8; 32] = [0xf6, 0x39, 0x1f, 0x5c, 0x32, 0xd9, 0xc6, 0x9d, 0x2a, 0x47, 0xea, 0x67, 0x0b, 0x44, 0x29, 0x74, 0xb5, 0x39, 0x35, 0xd1, 0xed, 0xc7, 0xfd, 0x64, 0xeb, 0x21, 0xe0, 0x47, 0xa8, 0x39, 0x17, 0x1b]; // RoleRevoked(bytes32,address,address)
+const EVENT_SCHEMA_MIGRATED: [u8; 32] = [0x4c, 0x18, 0xb3, 0x61, 0x38, 0x05, 0xe7, 0x27, 0x2f, 0x8d, 0x8f, 0xbd, 0xa3, 0x25, 0x7e, 0xd1, 0x0d, 0x1f, 0x16, 0x4d, 0x47, 0x95, 0x82, 0xf0, 0xc3, 0x8b, 0x52, 0x71, 0x68, 0xaa, 0x09, 0xd3]; // SchemaMigrated(uint256,uint256)
+const EVENT_UPGRADED: [u8; 32] = [0x24, 0x19, 0xee, 0xc9, 0xac, 0x1f, 0xe1, 0x3c, 0x83, 0xd0, 0x30, 0x32, 0x16, 0x83, 0x19, 0xf5, 0xea, 0x66, 0xfc, 0xc6, 0x4d, 0x7d, 0xd9, 0x5d, 0x7f, 0x3b, 0xc0, 0x45, 0x02, 0x52, 0x01, 0xd2]; // Upgraded(bytes32,bytes32)
+const EVENT_SUGGESTION_APPROVAL_SET: [u8; 32] = [0x9c, 0xd7, 0xcb, 0xec, 0x0f, 0x00, 0x4d, 0xaa, 0x2a, 0x4c, 0xef, 0x57, 0x6b, 0x60, 0xc1, 0xb7, 0xd0, 0xef, 0xc8, 0x1d, 0x04, 0x74, 0x67, 0x69, 0x7d, 0xd1, 0xd1, 0x9c, 0x16, 0xaa, 0x55, 0x1c]; // SuggestionApprovalSet(address,bool)
+const EVENT_SUGGESTION_APPROVED: [u8; 32] = [0x4f, 0xa2, 0x46, 0x0f, 0x5b, 0x48, 0x22, 0xa3, 0x37, 0xb9, 0x28, 0xd3, 0x54, 0x7b, 0xec, 0xe9, 0x21, 0x32, 0xe8, 0x42, 0xfc, 0xe9, 0x43, 0x9d, 0xe7, 0x25, 0x85, 0xb8, 0x9e, 0x50, 0x2e, 0x93]; // SuggestionApproved(address,uint256,address)
+const EVENT_SUGGESTION_REJECTED: [u8; 32] = [0x51, 0xd4, 0x85, 0xca, 0x2f, 0x55, 0xbb, 0xd7, 0xba, 0x15, 0x6f, 0x52, 0xfe, 0x68, 0x0f, 0xa9, 0x1b, 0x53, 0x4f, 0x83, 0x4d, 0xcc, 0x71, 0x75, 0xf5, 0xb7, 0x93, 0xa2, 0x89, 0xbd, 0xdc, 0xbd]; // SuggestionRejected(address,uint256,address)
+const EVENT_SUGGESTION_LIMITS_SET: [u8; 32] = [0xec, 0x33, 0xa8, 0x85, 0x3b, 0xf9, 0xb1, 0x4d, 0xa7, 0xf4, 0x58, 0xac, 0x8d, 0x21, 0xbc, 0x10, 0x60, 0xa4, 0x70, 0x07, 0x81, 0x75, 0x37, 0xce, 0x8d, 0x77, 0x0c, 0x04, 0x0d, 0xe7, 0x6b, 0xc8]; // SuggestionLimitsSet(address,uint256,uint256)
+const EVENT_REJECT_DUPLICATES_SET: [u8; 32] = [0x04, 0xd3, 0x8e, 0xb1, 0x7e, 0x7c, 0xd6, 0xae, 0xc7, 0x4e, 0xf4, 0x16, 0x31, 0xcc, 0x0a, 0x3a, 0xcf, 0x3e, 0x6b, 0xfd, 0xdd, 0x55, 0xd6, 0x95, 0x55, 0x3a, 0xff, 0x23, 0x29, 0xa0, 0xaf, 0x20]; // RejectDuplicatesSet(address,bool)
+const EVENT_SONGS_MERGED: [u8; 32] = [0xed, 0xca, 0x07, 0x90, 0x64, 0x3a, 0xef, 0x2e, 0x72, 0x6b, 0xa8, 0x0d, 0x85, 0x45, 0x7b, 0xe0, 0x07, 0x4e, 0xda, 0x69, 0xaf, 0xef, 0xcd, 0x32, 0x9e, 0xdc, 0x50, 0x4a, 0x74, 0xc0, 0xbb, 0xf1]; // SongsMerged(address,uint256,uint256,uint256)
+const EVENT_VOTE_BUDGET_SET: [u8; 32] = [0xe7, 0x47, 0xef, 0x5c, 0x2d, 0x8f, 0xe6, 0x04, 0x21, 0xac, 0x18, 0x95, 0xdb, 0xe9, 0xaf, 0xa1, 0xc3, 0xe1, 0xe9, 0x41, 0x1e, 0x7b, 0xc0, 0x34, 0xf7, 0x03, 0x98, 0x66, 0x44, 0x48, 0x26, 0x3c]; // VoteBudgetSet(address,uint256)
+const EVENT_DOWNVOTED: [u8; 32] = [0xac, 0xe1, 0xcd, 0x7b, 0x4a, 0x77, 0x40, 0x58, 0xb0, 0xf4, 0x84, 0xb1, 0x57, 0xeb, 0x8c, 0xb2, 0xec, 0x60, 0xb9, 0x7f, 0xf6, 0x73, 0x60, 0x06, 0xf3, 0xaa, 0x01, 0xd6, 0x83, 0x4b, 0xbe, 0xb6]; // Downvoted(address,uint256,address,uint256)
+const EVENT_UNDOWNVOTED: [u8; 32] = [0xd4, 0xba, 0x06, 0x00, 0x93, 0xbe, 0x18, 0x91, 0x31, 0x4f, 0x39, 0x08, 0xe7, 0x56, 0xbf, 0xee, 0x19, 0x79, 0x64, 0xbd, 0x2f, 0x6c, 0x78, 0x3f, 0x29, 0xca, 0x85, 0x8e, 0x63, 0xac, 0x30, 0xd7]; // Undownvoted(address,uint256,address,uint256)
+const EVENT_AUTO_HIDE_SET: [u8; 32] = [0xfd, 0xed, 0x75, 0xa2, 0x99, 0xf5, 0xe4, 0x46, 0x19, 0x09, 0xb1, 0x38, 0x17, 0xf3, 0xb0, 0x97, 0xfe, 0x17, 0x6e, 0x18, 0x5c, 0x80, 0xe8, 0xf7, 0xa4, 0x59, 0x7d, 0xa1, 0x97, 0xdb, 0xea, 0xbe]; // AutoHideSet(address,bool,int256)
+const EVENT_QUADRATIC_VOTING_SET: [u8; 32] = [0x39, 0x67, 0x18, 0xda, 0xef, 0x60, 0xa2, 0xe1, 0x18, 0x7f, 0x21, 0xff, 0xeb, 0xd1, 0xcd, 0xd0, 0xb9, 0xef, 0xa3, 0x25, 0x02, 0x28, 0xf1, 0xc2, 0xfb, 0xc0, 0x3e, 0x24, 0xb3, 0x7b, 0x2b, 0x07]; // QuadraticVotingSet(address,bool,uint256)
+const EVENT_VOTES_CAST: [u8; 32] = [0x84, 0x4b, 0xc2, 0x9e, 0x34, 0xe8, 0x01, 0xee, 0x93, 0x91, 0x71, 0x6d, 0xd8, 0x42, 0xe3, 0xd2, 0x2c, 0xd0, 0xe1, 0x66, 0xf3, 0x7e, 0x4e, 0xac, 0xe4, 0x10, 0x38, 0x1c, 0xcf, 0x38, 0x89, 0x82]; // VotesCast(address,uint256,address,uint256,uint256)
+const EVENT_VOTES_WITHDRAWN: [u8; 32] = [0x37, 0xb7, 0xf2, 0xfb, 0x04, 0x87, 0x5d, 0x5c, 0x5d, 0xc6, 0xf9, 0x1c, 0x2b, 0x28, 0x48, 0x6f, 0x2e, 0x5f, 0xf6, 0xa8, 0x1d, 0x3a, 0xdb, 0x20, 0xaa, 0x3b, 0x4f, 0x98, 0x3a, 0xfa, 0xef, 0x38]; // VotesWithdrawn(address,uint256,address,uint256,uint256)
+const EVENT_VOTE_DECAY_SET: [u8; 32] = [0x20, 0xc4, 0xaf, 0x55, 0xe0, 0x3a, 0x47, 0xc4, 0x82, 0xbc, 0x05, 0x09, 0x3d, 0xbf, 0x7b, 0x4a, 0x06, 0xcd, 0x44, 0xe5, 0x68, 0xa3, 0xc8, 0xe2, 0xee, 0xee, 0x18, 0x32, 0x15, 0x2c, 0xd7, 0x07]; // VoteDecaySet(address,uint256)
+const EVENT_VOTE_TOKEN_SET: [u8; 32] = [0x68, 0x17, 0x48, 0x88, 0x3e, 0xb9, 0x20, 0xe2, 0x56, 0xee, 0x0a, 0x9c, 0xe1, 0xc9, 0x84, 0x8a, 0x89, 0xa1, 0xeb, 0xdb, 0x57, 0xd2, 0x8e, 0x3a, 0xc3, 0x67, 0xc7, 0x50, 0x29, 0xda, 0x5f, 0x77]; // VoteTokenSet(address,address,uint8,uint256,uint256)
+const EVENT_GATE_SET: [u8; 32] = [0xd3, 0xe2, 0xb9, 0xea, 0x2f, 0x43, 0xc6, 0xd5, 0x1f, 0xb2, 0x8f, 0x50, 0xab, 0x56, 0x9b, 0x66, 0xea, 0x4f, 0x49, 0xe1, 0xb9, 0x53, 0xfa, 0xee, 0x5b, 0x10, 0x2f, 0x9a, 0xbb, 0x1c, 0x18, 0xa9]; // GateSet(address,uint8,address,uint256)
+
+// Custom error selectors (Solidity `error Name();`), returned as revert data
+const ERROR_NOT_OWNER: [u8; 4] = [0x30, 0xcd, 0x74, 0x71]; // NotOwner()
+const ERROR_NOT_DJ: [u8; 4] = [0x65, 0xd2, 0xe8, 0x52]; // NotDj()
+const ERROR_TARGET_NOT_DJ: [u8; 4] = [0x1c, 0xa2, 0xde, 0xb8]; // TargetNotDj()
+const ERROR_NOT_REGISTERED_DJ: [u8; 4] = [0x42, 0x23, 0x20, 0x1b]; // NotRegisteredDj()
+const ERROR_UNAUTHORIZED: [u8; 4] = [0x82, 0xb4, 0x29, 0x00]; // Unauthorized()
+const ERROR_DJ_NOT_ACTIVE: [u8; 4] = [0x78, 0x00, 0x5e, 0x25]; // DjNotActive()
+const ERROR_SET_NOT_ACTIVE: [u8; 4] = [0xad, 0x6c, 0xc2, 0xe1]; // SetNotActive()
+const ERROR_SET_ALREADY_ACTIVE: [u8; 4] = [0x0c, 0x3d, 0x19, 0xe6]; // SetAlreadyActive()
+const ERROR_EMPTY_SONG_NAME: [u8; 4] = [0x13, 0xa1, 0x93, 0xc8]; // EmptySongName()
+const ERROR_SONG_NAME_TOO_LONG: [u8; 4] = [0xbe, 0xf6, 0x4e, 0xc9]; // SongNameTooLong()
+const ERROR_SONG_NOT_FOUND: [u8; 4] = [0xf3, 0x3e, 0xc3, 0x7f]; // SongNotFound()
+const ERROR_SONG_WAS_REMOVED: [u8; 4] = [0x48, 0xd5, 0x8e, 0x09]; // SongWasRemoved()
+const ERROR_ALREADY_VOTED: [u8; 4] = [0x7c, 0x9a, 0x1c, 0xf9]; // AlreadyVoted()
+const ERROR_NOT_VOTED: [u8; 4] = [0xf8, 0xd4, 0x9b, 0xcd]; // NotVoted()
+const ERROR_METADATA_TOO_LONG: [u8; 4] = [0x85, 0xb8, 0xe2, 0xf4]; // MetadataTooLong()
+const ERROR_INVALID_PARAMS: [u8; 4] = [0xa8, 0x6b, 0x65, 0x12]; // InvalidParams()
+const ERROR_INVALID_INPUT: [u8; 4] = [0xb4, 0xfa, 0x3f, 0xb3]; // InvalidInput()
+const ERROR_QUEUE_EMPTY: [u8; 4] = [0x75, 0xe5, 0x2f, 0x4f]; // QueueEmpty()
+const ERROR_SONG_ALREADY_PLAYED: [u8; 4] = [0xc9, 0x4f, 0xaf, 0x69]; // SongAlreadyPlayed()
+const ERROR_NOT_DELEGATE: [u8; 4] = [0x1d, 0xb3, 0xb8, 0x59]; // NotDelegate()
+const ERROR_UNIVERSAL_REMOVER_IS_RETIRED: [u8; 4] = [0x94, 0x61, 0xf3, 0x3e]; // UniversalRemoverIsRetired()
+const ERROR_NOT_PENDING_OWNER: [u8; 4] = [0x18, 0x53, 0x97, 0x1c]; // NotPendingOwner()
+const ERROR_MISSING_ROLE: [u8; 4] = [0x94, 0x23, 0x59, 0x22]; // MissingRole()
+const ERROR_UNKNOWN_ROLE: [u8; 4] = [0x98, 0xb2, 0x10, 0x08]; // UnknownRole()
+const ERROR_SETS_ACTIVE: [u8; 4] = [0xa9, 0xde, 0x58, 0xb6]; // SetsActive()
+const ERROR_SOURCE_URI_TOO_LONG: [u8; 4] = [0xf1, 0xff, 0x69, 0x3a]; // SourceUriTooLong()
+const ERROR_SONG_PENDING: [u8; 4] = [0x33, 0xd0, 0x21, 0xd7]; // SongPending()
+const ERROR_NOT_PENDING: [u8; 4] = [0x7d, 0xc6, 0x50, 0x5a]; // NotPending()
+const ERROR_SUGGESTION_LIMIT_REACHED: [u8; 4] = [0x32, 0x50, 0x9b, 0xee]; // SuggestionLimitReached()
+const ERROR_SUGGESTION_TOO_SOON: [u8; 4] = [0x43, 0x7e, 0x2b, 0xf7]; // SuggestionTooSoon()
+const ERROR_DUPLICATE_SONG: [u8; 4] = [0x6e, 0x9e, 0x23, 0x3e]; // DuplicateSong()
+const ERROR_SONG_WAS_MERGED: [u8; 4] = [0x40, 0xe7, 0xbd, 0x3b]; // SongWasMerged()
+const ERROR_VOTE_BUDGET_EXCEEDED: [u8; 4] = [0xa7, 0xbc, 0x1b, 0x25]; // VoteBudgetExceeded()
+const ERROR_CONFLICTING_VOTE: [u8; 4] = [0x1a, 0xec, 0xa1, 0xe9]; // ConflictingVote()
+const ERROR_WRONG_VOTING_MODE: [u8; 4] = [0xd7, 0x69, 0xa5, 0x66]; // WrongVotingMode()
+const ERROR_INSUFFICIENT_CREDITS: [u8; 4] = [0x43, 0xfb, 0x94, 0x53]; // InsufficientCredits()
+const ERROR_NO_VOTING_WEIGHT: [u8; 4] = [0x92, 0x3d, 0x21, 0xf5]; // NoVotingWeight()
+const ERROR_TOKEN_CALL_FAILED: [u8; 4] = [0x3f, 0x40, 0x9f, 0x9a]; // TokenCallFailed()
+const ERROR_NOT_TICKET_HOLDER: [u8; 4] = [0x5a, 0x7f, 0x2a, 0x10]; // NotTicketHolder()
+const ERROR_SONG_HIDDEN: [u8; 4] = [0x96, 0x72, 0x7e, 0x3f]; // SongHidden()
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Error {
+    NotOwner,
+    NotDj,
+    TargetNotDj,
+    NotRegisteredDj,
+    Unauthorized,
+    DjNotActive,
+    SetNotActive,
+    SetAlreadyActive,
+    EmptySongName,
+    SongNameTooLong,
+    SongNotFound,
+    SongWasRemoved,
+    AlreadyVoted,
+    NotVoted,
+    MetadataTooLong,
+    QueueEmpty,
+    SongAlreadyPlayed,
+    NotDelegate,
+    UniversalRemoverIsRetired,
+    NotPendingOwner,
+    MissingRole,
+    UnknownRole,
+    SetsActive,
+    SourceUriTooLong,
+    SongPending,
+    NotPending,
+    SuggestionLimitReached,
+    SuggestionTooSoon,
+    DuplicateSong,
+    SongWasMerged,
+    VoteBudgetExceeded,
+    ConflictingVote,
+    WrongVotingMode,
+    InsufficientCredits,
+    NoVotingWeight,
+    TokenCallFailed,
+    NotTicketHolder,
+    SongHidden,
+    InvalidParams,
+    // Only raised by the PolkaVM `call` entry point
+    #[cfg_attr(test, allow(dead_code))]
+    InvalidInput,
+}
+
+impl Error {
+    fn selector(self) -> [u8; 4] {
+        match self {
+            Error::NotOwner => ERROR_NOT_OWNER,
+            Error::NotDj => ERROR_NOT_DJ,
+            Error::TargetNotDj => ERROR_TARGET_NOT_DJ,
+            Error::NotRegisteredDj => ERROR_NOT_REGISTERED_DJ,
+            Error::Unauthorized => ERROR_UNAUTHORIZED,
+            Error::DjNotActive => ERROR_DJ_NOT_ACTIVE,
+            Error::SetNotActive => ERROR_SET_NOT_ACTIVE,
+            Error::SetAlreadyActive => ERROR_SET_ALREADY_ACTIVE,
+            Error::EmptySongName => ERROR_EMPTY_SONG_NAME,
+            Error::SongNameTooLong => ERROR_SONG_NAME_TOO_LONG,
+            Error::SongNotFound => ERROR_SONG_NOT_FOUND,
+            Error::SongWasRemoved => ERROR_SONG_WAS_REMOVED,
+            Error::AlreadyVoted => ERROR_ALREADY_VOTED,
+            Error::NotVoted => ERROR_NOT_VOTED,
+            Error::MetadataTooLong => ERROR_METADATA_TOO_LONG,
+            Error::QueueEmpty => ERROR_QUEUE_EMPTY,
+            Error::SongAlreadyPlayed => ERROR_SONG_ALREADY_PLAYED,
+            Error::NotDelegate => ERROR_NOT_DELEGATE,
+            Error::UniversalRemoverIsRetired => ERROR_UNIVERSAL_REMOVER_IS_RETIRED,
+            Error::NotPendingOwner => ERROR_NOT_PENDING_OWNER,
+            Error::MissingRole => ERROR_MISSING_ROLE,
+            Error::UnknownRole => ERROR_UNKNOWN_ROLE,
+            Error::SetsActive => ERROR_SETS_ACTIVE,
+            Error::SourceUriTooLong => ERROR_SOURCE_URI_TOO_LONG,
+            Error::SongPending => ERROR_SONG_PENDING,
+            Error::NotPending => ERROR_NOT_PENDING,
+            Error::SuggestionLimitReached => ERROR_SUGGESTION_LIMIT_REACHED,
+            Error::SuggestionTooSoon => ERROR_SUGGESTION_TOO_SOON,
+            Error::DuplicateSong => ERROR_DUPLICATE_SONG,
+            Error::SongWasMerged => ERROR_SONG_WAS_MERGED,
+            Error::VoteBudgetExceeded => ERROR_VOTE_BUDGET_EXCEEDED,
+            Error::ConflictingVote => ERROR_CONFLICTING_VOTE,
+            Error::WrongVotingMode => ERROR_WRONG_VOTING_MODE,
+            Error::InsufficientCredits => ERROR_INSUFFICIENT_CREDITS,
+            Error::NoVotingWeight => ERROR_NO_VOTING_WEIGHT,
+            Error::TokenCallFailed => ERROR_TOKEN_CALL_FAILED,
+            Error::NotTicketHolder => ERROR_NOT_TICKET_HOLDER,
+            Error::SongHidden => ERROR_SONG_HIDDEN,
+            Error::InvalidParams => ERROR_INVALID_PARAMS,
+            Error::InvalidInput => ERROR_INVALID_INPUT,
+        }
+    }
+}
+
+// Early-return with a contract error when a precondition fails
+macro_rules! ensure {
+    ($cond:expr, $err:expr) => {
+        if !$cond {
+            return Err($err);
+        }
+    };
+}
 
 // Helper functions for storage keys
 fn get_dj_key(dj_address: &[u8; 20]) -> [u8; 32] {
@@ -62,50 +437,281 @@ fn get_dj_key(dj_address: &[u8; 20]) -> [u8; 32] {
     key
 }
 
-fn get_song_count_key(dj_address: &[u8; 20]) -> [u8; 32] {
+// Set-scoped keys append the set ID after the per-DJ fields, so set 0 lines up
+// with the layout used before sets were tracked and older queues stay readable
+fn get_song_count_key(dj_address: &[u8; 20], set_id: u32) -> [u8; 32] {
     let mut key = [0u8; 32];
     key[0] = PREFIX_SONG_COUNT;
     key[1..21].copy_from_slice(dj_address);
+    key[21..25].copy_from_slice(&set_id.to_le_bytes());
     key
 }
 
-fn get_song_key(dj_address: &[u8; 20], song_id: u32) -> [u8; 32] {
+fn get_song_key(dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
     let mut key = [0u8; 32];
     key[0] = PREFIX_SONG;
     key[1..21].copy_from_slice(dj_address);
     key[21..25].copy_from_slice(&song_id.to_le_bytes());
+    key[25..29].copy_from_slice(&set_id.to_le_bytes());
     key
 }
 
-fn get_song_removed_key(dj_address: &[u8; 20], song_id: u32) -> [u8; 32] {
+fn get_song_removed_key(dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
     let mut key = [0u8; 32];
     key[0] = PREFIX_SONG_REMOVED;
     key[1..21].copy_from_slice(dj_address);
     key[21..25].copy_from_slice(&song_id.to_le_bytes());
+    key[25..29].copy_from_slice(&set_id.to_le_bytes());
     key
 }
 
-fn get_votes_key(dj_address: &[u8; 20], song_id: u32) -> [u8; 32] {
+fn get_votes_key(dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
     let mut key = [0u8; 32];
     key[0] = PREFIX_VOTES;
     key[1..21].copy_from_slice(dj_address);
     key[21..25].copy_from_slice(&song_id.to_le_bytes());
+    key[25..29].copy_from_slice(&set_id.to_le_bytes());
     key
 }
 
-fn get_has_voted_key(voter: &[u8; 20], dj_address: &[u8; 20], song_id: u32) -> [u8; 32] {
+fn get_has_voted_key(env: &impl Env, voter: &[u8; 20], dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
     let mut key = [0u8; 32];
     key[0] = PREFIX_HAS_VOTED;
+    let mut data = [0u8; 48];
+    data[..20].copy_from_slice(voter);
+    data[20..40].copy_from_slice(dj_address);
+    data[40..44].copy_from_slice(&song_id.to_le_bytes());
+    data[44..48].copy_from_slice(&set_id.to_le_bytes());
+    // Set 0 hashes the original 44-byte preimage
+    let len = if set_id == 0 { 44 } else { 48 };
+    let hash = env.hash_keccak_256(&data[..len]);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+// Per-song attributes share the song key layout under their own prefix
+fn get_song_attribute_key(prefix: u8, dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = prefix;
+    key[1..21].copy_from_slice(dj_address);
+    key[21..25].copy_from_slice(&song_id.to_le_bytes());
+    key[25..29].copy_from_slice(&set_id.to_le_bytes());
+    key
+}
+
+fn get_suggester_song_key(env: &impl Env, dj_address: &[u8; 20], set_id: u32, suggester: &[u8; 20], index: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SUGGESTER_SONGS;
+    let mut data = [0u8; 48];
+    data[..20].copy_from_slice(dj_address);
+    data[20..40].copy_from_slice(suggester);
+    data[40..44].copy_from_slice(&set_id.to_le_bytes());
+    data[44..48].copy_from_slice(&index.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_suggester_song_count_key(env: &impl Env, dj_address: &[u8; 20], set_id: u32, suggester: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SUGGESTER_SONG_COUNT;
+    let mut data = [0u8; 44];
+    data[..20].copy_from_slice(dj_address);
+    data[20..40].copy_from_slice(suggester);
+    data[40..44].copy_from_slice(&set_id.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_suggestion_approval_key(dj_address: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SUGGESTION_APPROVAL;
+    key[1..21].copy_from_slice(dj_address);
+    key
+}
+
+// `field` 0 holds the per-set maximum, 1 the minimum interval
+fn get_suggestion_limits_key(dj_address: &[u8; 20], field: u8) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SUGGESTION_LIMITS;
+    key[1..21].copy_from_slice(dj_address);
+    key[21] = field;
+    key
+}
+
+// Maps a normalized song key to song ID + 1 within one set of a DJ
+fn get_song_index_key(env: &impl Env, dj_address: &[u8; 20], set_id: u32, song_key: &[u8; 32]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SONG_INDEX;
+    let mut data = [0u8; 56];
+    data[..20].copy_from_slice(dj_address);
+    data[20..24].copy_from_slice(&set_id.to_le_bytes());
+    data[24..56].copy_from_slice(song_key);
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_reject_duplicates_key(dj_address: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_REJECT_DUPLICATES;
+    key[1..21].copy_from_slice(dj_address);
+    key
+}
+
+fn get_song_voter_key(env: &impl Env, dj_address: &[u8; 20], set_id: u32, song_id: u32, index: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SONG_VOTERS;
+    let mut data = [0u8; 32];
+    data[..20].copy_from_slice(dj_address);
+    data[20..24].copy_from_slice(&set_id.to_le_bytes());
+    data[24..28].copy_from_slice(&song_id.to_le_bytes());
+    data[28..32].copy_from_slice(&index.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_song_voter_listed_key(env: &impl Env, voter: &[u8; 20], dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SONG_VOTER_LISTED;
+    let mut data = [0u8; 48];
+    data[..20].copy_from_slice(voter);
+    data[20..40].copy_from_slice(dj_address);
+    data[40..44].copy_from_slice(&song_id.to_le_bytes());
+    data[44..48].copy_from_slice(&set_id.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_vote_budget_key(dj_address: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_VOTE_BUDGET;
+    key[1..21].copy_from_slice(dj_address);
+    key
+}
+
+fn get_votes_spent_key(env: &impl Env, voter: &[u8; 20], dj_address: &[u8; 20], set_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_VOTES_SPENT;
+    let mut data = [0u8; 44];
+    data[..20].copy_from_slice(voter);
+    data[20..40].copy_from_slice(dj_address);
+    data[40..44].copy_from_slice(&set_id.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_has_downvoted_key(env: &impl Env, voter: &[u8; 20], dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_HAS_DOWNVOTED;
+    let mut data = [0u8; 48];
+    data[..20].copy_from_slice(voter);
+    data[20..40].copy_from_slice(dj_address);
+    data[40..44].copy_from_slice(&song_id.to_le_bytes());
+    data[44..48].copy_from_slice(&set_id.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_downvote_weight_key(env: &impl Env, voter: &[u8; 20], dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_DOWNVOTE_WEIGHT;
+    let mut data = [0u8; 48];
+    data[..20].copy_from_slice(voter);
+    data[20..40].copy_from_slice(dj_address);
+    data[40..44].copy_from_slice(&song_id.to_le_bytes());
+    data[44..48].copy_from_slice(&set_id.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+// `field` 0 holds whether auto-hide is on, 1 the net score threshold
+fn get_auto_hide_key(dj_address: &[u8; 20], field: u8) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_AUTO_HIDE;
+    key[1..21].copy_from_slice(dj_address);
+    key[21] = field;
+    key
+}
+
+// `field` 0 holds whether quadratic voting is on, 1 the credits per voter per set
+fn get_quadratic_voting_key(dj_address: &[u8; 20], field: u8) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_QUADRATIC_VOTING;
+    key[1..21].copy_from_slice(dj_address);
+    key[21] = field;
+    key
+}
+
+fn get_votes_cast_key(env: &impl Env, voter: &[u8; 20], dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_VOTES_CAST;
+    let mut data = [0u8; 48];
+    data[..20].copy_from_slice(voter);
+    data[20..40].copy_from_slice(dj_address);
+    data[40..44].copy_from_slice(&song_id.to_le_bytes());
+    data[44..48].copy_from_slice(&set_id.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_credits_spent_key(env: &impl Env, voter: &[u8; 20], dj_address: &[u8; 20], set_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_CREDITS_SPENT;
     let mut data = [0u8; 44];
     data[..20].copy_from_slice(voter);
     data[20..40].copy_from_slice(dj_address);
+    data[40..44].copy_from_slice(&set_id.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_vote_decay_key(dj_address: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_VOTE_DECAY;
+    key[1..21].copy_from_slice(dj_address);
+    key
+}
+
+fn get_voted_at_key(env: &impl Env, voter: &[u8; 20], dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_VOTED_AT;
+    let mut data = [0u8; 48];
+    data[..20].copy_from_slice(voter);
+    data[20..40].copy_from_slice(dj_address);
     data[40..44].copy_from_slice(&song_id.to_le_bytes());
-    let mut hash = [0u8; 32];
-    api::hash_keccak_256(&data, &mut hash);
+    data[44..48].copy_from_slice(&set_id.to_le_bytes());
+    let hash = env.hash_keccak_256(&data);
     key[1..].copy_from_slice(&hash[..31]);
     key
 }
 
+// `field` 0 holds the token address, 1 the weight curve, 2 the balance per vote and 3 the cap
+fn get_vote_token_key(dj_address: &[u8; 20], field: u8) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_VOTE_TOKEN;
+    key[1..21].copy_from_slice(dj_address);
+    key[21] = field;
+    key
+}
+
+// `field` 0 holds the gate kind, 1 the ticket contract and 2 the ERC-1155 token ID
+fn get_gate_key(dj_address: &[u8; 20], field: u8) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_GATE;
+    key[1..21].copy_from_slice(dj_address);
+    key[21] = field;
+    key
+}
+
 fn get_set_active_key(dj_address: &[u8; 20]) -> [u8; 32] {
     let mut key = [0u8; 32];
     key[0] = PREFIX_SET_ACTIVE;
@@ -113,17 +719,61 @@ fn get_set_active_key(dj_address: &[u8; 20]) -> [u8; 32] {
     key
 }
 
-fn get_set_start_time_key(dj_address: &[u8; 20]) -> [u8; 32] {
+fn get_current_set_key(dj_address: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_CURRENT_SET;
+    key[1..21].copy_from_slice(dj_address);
+    key
+}
+
+fn get_set_start_time_key(dj_address: &[u8; 20], set_id: u32) -> [u8; 32] {
     let mut key = [0u8; 32];
     key[0] = PREFIX_SET_START_TIME;
     key[1..21].copy_from_slice(dj_address);
+    key[21..25].copy_from_slice(&set_id.to_le_bytes());
     key
 }
 
-fn get_set_end_time_key(dj_address: &[u8; 20]) -> [u8; 32] {
+fn get_set_end_time_key(dj_address: &[u8; 20], set_id: u32) -> [u8; 32] {
     let mut key = [0u8; 32];
     key[0] = PREFIX_SET_END_TIME;
     key[1..21].copy_from_slice(dj_address);
+    key[21..25].copy_from_slice(&set_id.to_le_bytes());
+    key
+}
+
+// Song count a set had when it stopped; songs added after that are the off-air queue
+fn get_set_queue_start_key(dj_address: &[u8; 20], set_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SET_QUEUE_START;
+    key[1..21].copy_from_slice(dj_address);
+    key[21..25].copy_from_slice(&set_id.to_le_bytes());
+    key
+}
+
+fn get_song_played_at_key(dj_address: &[u8; 20], set_id: u32, song_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SONG_PLAYED_AT;
+    key[1..21].copy_from_slice(dj_address);
+    key[21..25].copy_from_slice(&song_id.to_le_bytes());
+    key[25..29].copy_from_slice(&set_id.to_le_bytes());
+    key
+}
+
+fn get_play_history_key(dj_address: &[u8; 20], set_id: u32, index: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_PLAY_HISTORY;
+    key[1..21].copy_from_slice(dj_address);
+    key[21..25].copy_from_slice(&index.to_le_bytes());
+    key[25..29].copy_from_slice(&set_id.to_le_bytes());
+    key
+}
+
+fn get_play_count_key(dj_address: &[u8; 20], set_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_PLAY_COUNT;
+    key[1..21].copy_from_slice(dj_address);
+    key[21..25].copy_from_slice(&set_id.to_le_bytes());
     key
 }
 
@@ -160,784 +810,3169 @@ fn get_all_dj_count_key() -> [u8; 32] {
     key
 }
 
+fn get_delegate_permissions_key(env: &impl Env, dj_address: &[u8; 20], delegate: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_DELEGATE_PERMISSIONS;
+    let mut data = [0u8; 40];
+    data[..20].copy_from_slice(dj_address);
+    data[20..40].copy_from_slice(delegate);
+    let hash = env.hash_keccak_256(&data);
+    key[1..].copy_from_slice(&hash[..31]);
+    key
+}
+
+fn get_delegate_key(dj_address: &[u8; 20], index: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_DELEGATES;
+    key[1..21].copy_from_slice(dj_address);
+    key[21..25].copy_from_slice(&index.to_le_bytes());
+    key
+}
+
+fn get_delegate_count_key(dj_address: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_DELEGATE_COUNT;
+    key[1..21].copy_from_slice(dj_address);
+    key
+}
+
+fn get_universal_remover_retired_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_UNIVERSAL_REMOVER_RETIRED;
+    key
+}
+
+fn get_pending_owner_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_PENDING_OWNER;
+    key
+}
+
+fn get_role_member_key(role: u8, account: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_ROLE_MEMBER;
+    key[1] = role;
+    key[2..22].copy_from_slice(account);
+    key
+}
+
+fn get_role_members_key(role: u8, index: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_ROLE_MEMBERS;
+    key[1] = role;
+    key[2..6].copy_from_slice(&index.to_le_bytes());
+    key
+}
+
+fn get_role_member_count_key(role: u8) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_ROLE_MEMBER_COUNT;
+    key[1] = role;
+    key
+}
+
+fn get_schema_version_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_SCHEMA_VERSION;
+    key
+}
+
+// Position of an in-progress migration; `field` selects the DJ index, set ID, song ID or
+// whether the current DJ's metadata is done
+fn get_migration_cursor_key(field: u8) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_MIGRATION_CURSOR;
+    key[1] = field;
+    key
+}
+
+fn get_previous_code_hash_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[0] = PREFIX_PREVIOUS_CODE_HASH;
+    key
+}
+
 // Storage helpers
-fn save_address(key: &[u8; 32], address: &[u8; 20]) {
-    api::set_storage(StorageFlags::empty(), key, address);
+fn save_address(env: &mut impl Env, key: &[u8; 32], address: &[u8; 20]) {
+    env.set_storage(key, address);
 }
 
-fn get_address(key: &[u8; 32]) -> Option<[u8; 20]> {
+fn get_address(env: &impl Env, key: &[u8; 32]) -> Option<[u8; 20]> {
     let mut buffer = [0u8; 20];
-    match api::get_storage(StorageFlags::empty(), key, &mut &mut buffer[..]) {
-        Ok(_) => Some(buffer),
-        Err(_) => None,
-    }
+    env.get_storage(key, &mut buffer).map(|_| buffer)
 }
 
-fn save_bool(key: &[u8; 32], value: bool) {
-    api::set_storage(StorageFlags::empty(), key, &[value as u8]);
+fn save_bool(env: &mut impl Env, key: &[u8; 32], value: bool) {
+    env.set_storage(key, &[value as u8]);
 }
 
-fn get_bool(key: &[u8; 32]) -> bool {
+fn get_bool(env: &impl Env, key: &[u8; 32]) -> bool {
     let mut buffer = [0u8; 1];
-    match api::get_storage(StorageFlags::empty(), key, &mut &mut buffer[..]) {
-        Ok(_) => buffer[0] != 0,
-        Err(_) => false,
+    match env.get_storage(key, &mut buffer) {
+        Some(_) => buffer[0] != 0,
+        None => false,
     }
 }
 
-fn save_u32(key: &[u8; 32], value: u32) {
-    api::set_storage(StorageFlags::empty(), key, &value.to_le_bytes());
+fn save_u32(env: &mut impl Env, key: &[u8; 32], value: u32) {
+    env.set_storage(key, &value.to_le_bytes());
 }
 
-fn get_u32(key: &[u8; 32]) -> u32 {
+fn get_u32(env: &impl Env, key: &[u8; 32]) -> u32 {
     let mut buffer = [0u8; 4];
-    match api::get_storage(StorageFlags::empty(), key, &mut &mut buffer[..]) {
-        Ok(_) => u32::from_le_bytes(buffer),
-        Err(_) => 0,
+    match env.get_storage(key, &mut buffer) {
+        Some(_) => u32::from_le_bytes(buffer),
+        None => 0,
     }
 }
 
-fn save_string(key: &[u8; 32], value: &[u8]) {
-    api::set_storage(StorageFlags::empty(), key, value);
+fn save_string(env: &mut impl Env, key: &[u8; 32], value: &[u8]) {
+    let mut encoded = Vec::with_capacity(STRING_HEADER_LEN + value.len());
+    encoded.push(STRING_MARKER);
+    encoded.push(STRING_ENCODING_V1);
+    encoded.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    encoded.extend_from_slice(value);
+    env.set_storage(key, &encoded);
 }
 
-fn get_string(key: &[u8; 32]) -> Option<Vec<u8>> {
-    // Try with a reasonable buffer size
-    let mut buffer = vec![0u8; 256];
-    let mut buf_ref = &mut buffer[..];
-    match api::get_storage(StorageFlags::empty(), key, &mut buf_ref) {
-        Ok(_) => {
-            // Find actual length by looking for trailing zeros
-            let len = buffer.iter().rposition(|&b| b != 0).map(|i| i + 1).unwrap_or(0);
-            if len == 0 {
-                None
-            } else {
-                Some(buffer[..len].to_vec())
-            }
-        },
-        Err(_) => None,
+// Reads a string written by `save_string`, or a headerless value from before the
+// encoding was versioned; `max_len` is the field's limit and sizes the read buffer
+fn get_string(env: &impl Env, key: &[u8; 32], max_len: usize) -> Option<Vec<u8>> {
+    let mut buffer = vec![0u8; STRING_HEADER_LEN + max_len];
+    let read = env.get_storage(key, &mut buffer)?;
+    
+    if read >= STRING_HEADER_LEN && buffer[0] == STRING_MARKER {
+        if buffer[1] != STRING_ENCODING_V1 {
+            return None;
+        }
+        let len = u16::from_le_bytes([buffer[2], buffer[3]]) as usize;
+        let end = (STRING_HEADER_LEN + len).min(read);
+        return Some(buffer[STRING_HEADER_LEN..end].to_vec());
+    }
+    
+    // Legacy values are the raw bytes; an empty one was never written by the contract
+    if read == 0 {
+        None
+    } else {
+        Some(buffer[..read].to_vec())
     }
 }
 
-fn is_owner(origin: &[u8; 20]) -> bool {
-    match get_address(&KEY_OWNER) {
-        Some(owner) => owner == *origin,
-        None => false,
+// Rewrites a headerless value in the current encoding; returns whether anything changed
+fn reencode_string(env: &mut impl Env, key: &[u8; 32], max_len: usize) -> bool {
+    let mut buffer = vec![0u8; STRING_HEADER_LEN + max_len];
+    match env.get_storage(key, &mut buffer) {
+        Some(read) if read > 0 && buffer[0] != STRING_MARKER => {
+            save_string(env, key, &buffer[..read]);
+            true
+        },
+        _ => false,
     }
 }
 
-fn get_origin() -> [u8; 20] {
-    let mut origin = [0u8; 20];
-    api::caller(&mut origin);
-    origin
+// Event helpers
+fn address_topic(address: &[u8; 20]) -> [u8; 32] {
+    let mut topic = [0u8; 32];
+    topic[12..].copy_from_slice(address);
+    topic
 }
 
-// Contract functions
-fn register_dj(dj_address: [u8; 20]) {
-    let origin = get_origin();
-    
-    assert!(is_owner(&origin), "NOT_OWNER");
-    
-    // Check if already registered
-    let dj_key = get_dj_key(&dj_address);
-    if !get_bool(&dj_key) {
-        // Save DJ as registered
-        save_bool(&dj_key, true);
-        
-        // Add to all DJs list
-        let count_key = get_all_dj_count_key();
-        let count = get_u32(&count_key);
-        let dj_list_key = get_all_dj_key(count);
-        save_address(&dj_list_key, &dj_address);
-        save_u32(&count_key, count + 1);
+fn u32_topic(value: u32) -> [u8; 32] {
+    let mut topic = [0u8; 32];
+    topic[28..].copy_from_slice(&value.to_be_bytes());
+    topic
+}
+
+// Topics are [signature, indexed params...]; data holds the ABI-encoded non-indexed params
+fn emit_event(env: &mut impl Env, topics: &[[u8; 32]], data: &[Token]) {
+    env.deposit_event(topics, &encode(data));
+}
+
+fn is_owner(env: &impl Env, origin: &[u8; 20]) -> bool {
+    match get_address(env, &KEY_OWNER) {
+        Some(owner) => owner == *origin && owner != [0u8; 20],
+        None => false,
     }
 }
 
-fn remove_dj(dj_address: [u8; 20]) {
-    let origin = get_origin();
-    
-    assert!(is_owner(&origin), "NOT_OWNER");
-    
-    let dj_key = get_dj_key(&dj_address);
-    save_bool(&dj_key, false);
+// Current owner; the zero address once ownership has been renounced
+fn get_owner(env: &impl Env) -> [u8; 20] {
+    get_address(env, &KEY_OWNER).unwrap_or([0u8; 20])
 }
 
-fn is_dj(dj_address: [u8; 20]) -> bool {
-    get_bool(&get_dj_key(&dj_address))
+// Address nominated by `transfer_ownership`; the zero address if none
+fn get_pending_owner(env: &impl Env) -> [u8; 20] {
+    get_address(env, &get_pending_owner_key()).unwrap_or([0u8; 20])
 }
 
-fn add_song(song_name: Vec<u8>) -> u32 {
-    let origin = get_origin();
+// Ownership changes hands in two steps: the owner nominates, the nominee accepts
+fn transfer_ownership(env: &mut impl Env, new_owner: [u8; 20]) -> Result<(), Error> {
+    let origin = env.caller();
     
-    assert!(get_bool(&get_dj_key(&origin)), "NOT_DJ");
-    assert!(song_name.len() > 0, "EMPTY_SONG_NAME");
-    assert!(song_name.len() <= 256, "SONG_NAME_TOO_LONG");
+    ensure!(is_owner(env, &origin), Error::NotOwner);
     
-    let count_key = get_song_count_key(&origin);
-    let song_id = get_u32(&count_key);
+    save_address(env, &get_pending_owner_key(), &new_owner);
     
-    let song_key = get_song_key(&origin, song_id);
-    save_string(&song_key, &song_name);
+    emit_event(env, &[EVENT_OWNERSHIP_TRANSFER_STARTED, address_topic(&origin), address_topic(&new_owner)], &[]);
     
-    save_u32(&count_key, song_id + 1);
-    
-    song_id
+    Ok(())
 }
 
-fn suggest_song(dj_address: [u8; 20], song_name: Vec<u8>) -> u32 {
-    // Check that the target is a registered DJ
-    assert!(get_bool(&get_dj_key(&dj_address)), "TARGET_NOT_DJ");
+fn accept_ownership(env: &mut impl Env) -> Result<(), Error> {
+    let origin = env.caller();
     
-    // Check that the DJ is currently active
-    assert!(get_bool(&get_set_active_key(&dj_address)), "DJ_NOT_ACTIVE");
+    let pending_owner = get_pending_owner(env);
+    ensure!(pending_owner != [0u8; 20] && pending_owner == origin, Error::NotPendingOwner);
     
-    // Validate song name
-    assert!(song_name.len() > 0, "EMPTY_SONG_NAME");
-    assert!(song_name.len() <= 256, "SONG_NAME_TOO_LONG");
+    let previous_owner = get_owner(env);
+    save_address(env, &KEY_OWNER, &origin);
+    save_address(env, &get_pending_owner_key(), &[0u8; 20]);
     
-    // Add song to DJ's queue
-    let count_key = get_song_count_key(&dj_address);
-    let song_id = get_u32(&count_key);
+    emit_event(env, &[EVENT_OWNERSHIP_TRANSFERRED, address_topic(&previous_owner), address_topic(&origin)], &[]);
     
-    let song_key = get_song_key(&dj_address, song_id);
-    save_string(&song_key, &song_name);
+    Ok(())
+}
+
+// Leaves the contract without an owner; owner-only functions become unreachable
+fn renounce_ownership(env: &mut impl Env) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_owner(env, &origin), Error::NotOwner);
+    
+    save_address(env, &KEY_OWNER, &[0u8; 20]);
+    save_address(env, &get_pending_owner_key(), &[0u8; 20]);
+    
+    emit_event(env, &[EVENT_OWNERSHIP_TRANSFERRED, address_topic(&origin), address_topic(&[0u8; 20])], &[]);
+    
+    Ok(())
+}
+
+// Roles: named privileges the owner (or an admin) hands out to event staff
+
+// Compact storage index for a role identifier
+fn role_index(role: &[u8; 32]) -> Result<u8, Error> {
+    match *role {
+        ROLE_ADMIN => Ok(0),
+        ROLE_DJ_MANAGER => Ok(1),
+        ROLE_MODERATOR => Ok(2),
+        _ => Err(Error::UnknownRole),
+    }
+}
+
+fn has_role(env: &impl Env, role: &[u8; 32], account: [u8; 20]) -> Result<bool, Error> {
+    let index = role_index(role)?;
+    Ok(get_bool(env, &get_role_member_key(index, &account)))
+}
+
+// The owner and admins hold every role implicitly
+fn is_authorized_for(env: &impl Env, role: &[u8; 32], account: [u8; 20]) -> bool {
+    is_owner(env, &account)
+        || has_role(env, &ROLE_ADMIN, account).unwrap_or(false)
+        || has_role(env, role, account).unwrap_or(false)
+}
+
+fn grant_role(env: &mut impl Env, role: [u8; 32], account: [u8; 20]) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    let index = role_index(&role)?;
+    ensure!(is_authorized_for(env, &ROLE_ADMIN, origin), Error::MissingRole);
+    
+    let member_key = get_role_member_key(index, &account);
+    if !get_bool(env, &member_key) {
+        save_bool(env, &member_key, true);
+        
+        let count_key = get_role_member_count_key(index);
+        let count = get_u32(env, &count_key);
+        save_address(env, &get_role_members_key(index, count), &account);
+        save_u32(env, &count_key, count + 1);
+        
+        emit_event(env, &[EVENT_ROLE_GRANTED, role, address_topic(&account), address_topic(&origin)], &[]);
+    }
+    
+    Ok(())
+}
+
+fn revoke_role(env: &mut impl Env, role: [u8; 32], account: [u8; 20]) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    let index = role_index(&role)?;
+    ensure!(is_authorized_for(env, &ROLE_ADMIN, origin), Error::MissingRole);
+    
+    let member_key = get_role_member_key(index, &account);
+    if get_bool(env, &member_key) {
+        save_bool(env, &member_key, false);
+        
+        // Move the last member into the freed slot
+        let count_key = get_role_member_count_key(index);
+        let count = get_u32(env, &count_key);
+        for i in 0..count {
+            if get_address(env, &get_role_members_key(index, i)) == Some(account) {
+                if i < count - 1 {
+                    if let Some(last) = get_address(env, &get_role_members_key(index, count - 1)) {
+                        save_address(env, &get_role_members_key(index, i), &last);
+                    }
+                }
+                save_u32(env, &count_key, count - 1);
+                break;
+            }
+        }
+        
+        emit_event(env, &[EVENT_ROLE_REVOKED, role, address_topic(&account), address_topic(&origin)], &[]);
+    }
+    
+    Ok(())
+}
+
+fn get_role_members(env: &impl Env, role: &[u8; 32]) -> Result<Vec<[u8; 20]>, Error> {
+    let index = role_index(role)?;
+    let count = get_u32(env, &get_role_member_count_key(index));
+    let mut members = Vec::new();
+    
+    for i in 0..count {
+        if let Some(member) = get_address(env, &get_role_members_key(index, i)) {
+            members.push(member);
+        }
+    }
+    
+    Ok(members)
+}
+
+// Contract functions
+fn register_dj(env: &mut impl Env, dj_address: [u8; 20]) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_authorized_for(env, &ROLE_DJ_MANAGER, origin), Error::MissingRole);
+    
+    // Check if already registered
+    let dj_key = get_dj_key(&dj_address);
+    if !get_bool(env, &dj_key) {
+        // Save DJ as registered
+        save_bool(env, &dj_key, true);
+        
+        // Add to all DJs list
+        let count_key = get_all_dj_count_key();
+        let count = get_u32(env, &count_key);
+        let dj_list_key = get_all_dj_key(count);
+        save_address(env, &dj_list_key, &dj_address);
+        save_u32(env, &count_key, count + 1);
+        
+        emit_event(env, &[EVENT_DJ_REGISTERED, address_topic(&dj_address)], &[]);
+    }
+    
+    Ok(())
+}
+
+fn remove_dj(env: &mut impl Env, dj_address: [u8; 20]) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_authorized_for(env, &ROLE_DJ_MANAGER, origin), Error::MissingRole);
+    
+    let dj_key = get_dj_key(&dj_address);
+    if get_bool(env, &dj_key) {
+        save_bool(env, &dj_key, false);
+        
+        emit_event(env, &[EVENT_DJ_REMOVED, address_topic(&dj_address)], &[]);
+    }
+    
+    Ok(())
+}
+
+// The DJ and the owner can act in any room; delegates only where their permissions allow
+fn can_act_for(env: &impl Env, origin: &[u8; 20], dj_address: [u8; 20], permission: u32) -> bool {
+    *origin == dj_address
+        || is_owner(env, origin)
+        || get_delegate_permissions(env, dj_address, *origin) & permission != 0
+}
+
+fn is_dj(env: &impl Env, dj_address: [u8; 20]) -> bool {
+    get_bool(env, &get_dj_key(&dj_address))
+}
+
+fn add_song(env: &mut impl Env, song_name: Vec<u8>) -> Result<u32, Error> {
+    add_song_entry(env, song_name, &[]).map(|(song_id, _)| song_id)
+}
+
+// Adds a song, or resolves a duplicate per the DJ's policy; returns (song_id, created)
+fn add_song_entry(env: &mut impl Env, song_name: Vec<u8>, source_uri: &[u8]) -> Result<(u32, bool), Error> {
+    let origin = env.caller();
+    
+    ensure!(get_bool(env, &get_dj_key(&origin)), Error::NotDj);
+    ensure!(!song_name.is_empty(), Error::EmptySongName);
+    ensure!(song_name.len() <= MAX_SONG_NAME_LEN, Error::SongNameTooLong);
+    
+    let set_id = get_current_set_id(env, origin);
+    let dedup_key = normalized_song_key(env, &song_name, source_uri);
+    if let Some(existing) = find_duplicate(env, origin, set_id, &dedup_key)? {
+        return Ok((existing, false));
+    }
+    
+    let count_key = get_song_count_key(&origin, set_id);
+    let song_id = get_u32(env, &count_key);
+    
+    let song_key = get_song_key(&origin, set_id, song_id);
+    save_string(env, &song_key, &song_name);
+    save_song_origin(env, origin, set_id, song_id);
+    save_u32(env, &get_song_index_key(env, &origin, set_id, &dedup_key), song_id + 1);
+    
+    save_u32(env, &count_key, song_id + 1);
+    
+    emit_event(
+        env,
+        &[EVENT_SONG_ADDED, address_topic(&origin), u32_topic(song_id)],
+        &[Token::String(String::from_utf8_lossy(&song_name).into_owned())],
+    );
+    
+    Ok((song_id, true))
+}
+
+fn suggest_song(env: &mut impl Env, dj_address: [u8; 20], song_name: Vec<u8>) -> Result<u32, Error> {
+    suggest_song_entry(env, dj_address, song_name, &[]).map(|(song_id, _)| song_id)
+}
+
+// Suggests a song, or resolves a duplicate per the DJ's policy; returns (song_id, created)
+fn suggest_song_entry(env: &mut impl Env, dj_address: [u8; 20], song_name: Vec<u8>, source_uri: &[u8]) -> Result<(u32, bool), Error> {
+    // Check that the target is a registered DJ
+    ensure!(get_bool(env, &get_dj_key(&dj_address)), Error::TargetNotDj);
+    
+    // Check that the DJ is currently active
+    ensure!(get_bool(env, &get_set_active_key(&dj_address)), Error::DjNotActive);
+    ensure!(holds_ticket(env, dj_address, env.caller())?, Error::NotTicketHolder);
+    
+    // Validate song name
+    ensure!(!song_name.is_empty(), Error::EmptySongName);
+    ensure!(song_name.len() <= MAX_SONG_NAME_LEN, Error::SongNameTooLong);
+    
+    // An existing entry takes the suggestion instead of a new one. Nothing is written for
+    // it, so it is exempt from the throttle below and doesn't count against it
+    let set_id = get_current_set_id(env, dj_address);
+    let dedup_key = normalized_song_key(env, &song_name, source_uri);
+    if let Some(existing) = find_duplicate(env, dj_address, set_id, &dedup_key)? {
+        return Ok((existing, false));
+    }
+    
+    // Enforce the DJ's per-address throttle
+    let (remaining, next_allowed_at) = get_suggestion_allowance(env, dj_address, set_id, env.caller());
+    ensure!(remaining != Some(0), Error::SuggestionLimitReached);
+    ensure!(env.now() >= next_allowed_at, Error::SuggestionTooSoon);
+    
+    // Add song to the live set's queue
+    let count_key = get_song_count_key(&dj_address, set_id);
+    let song_id = get_u32(env, &count_key);
+    
+    let song_key = get_song_key(&dj_address, set_id, song_id);
+    save_string(env, &song_key, &song_name);
+    save_song_origin(env, dj_address, set_id, song_id);
+    
+    // Flag it as an audience request and index it under the suggester
+    let suggester = env.caller();
+    save_bool(env, &get_song_attribute_key(PREFIX_SONG_SUGGESTED, &dj_address, set_id, song_id), true);
+    if requires_suggestion_approval(env, dj_address) {
+        save_bool(env, &get_song_attribute_key(PREFIX_SONG_PENDING, &dj_address, set_id, song_id), true);
+    }
+    let suggester_count_key = get_suggester_song_count_key(env, &dj_address, set_id, &suggester);
+    let suggester_count = get_u32(env, &suggester_count_key);
+    save_u32(env, &get_suggester_song_key(env, &dj_address, set_id, &suggester, suggester_count), song_id);
+    save_u32(env, &suggester_count_key, suggester_count + 1);
+    save_u32(env, &get_song_index_key(env, &dj_address, set_id, &dedup_key), song_id + 1);
+    
+    save_u32(env, &count_key, song_id + 1);
+    
+    emit_event(
+        env,
+        &[EVENT_SONG_SUGGESTED, address_topic(&dj_address), u32_topic(song_id), address_topic(&env.caller())],
+        &[Token::String(String::from_utf8_lossy(&song_name).into_owned())],
+    );
+    
+    Ok((song_id, true))
+}
+
+// Duplicate detection: songs are identified by their source URI when they have one,
+// otherwise by the name with ASCII case folded and whitespace runs collapsed
+fn normalized_song_key(env: &impl Env, song_name: &[u8], source_uri: &[u8]) -> [u8; 32] {
+    if !source_uri.is_empty() {
+        let mut data = b"uri:".to_vec();
+        data.extend_from_slice(source_uri);
+        return env.hash_keccak_256(&data);
+    }
+    
+    let mut data = b"name:".to_vec();
+    for word in song_name.split(|b| b.is_ascii_whitespace()).filter(|word| !word.is_empty()) {
+        if data.len() > 5 {
+            data.push(b' ');
+        }
+        data.extend(word.iter().map(u8::to_ascii_lowercase));
+    }
+    env.hash_keccak_256(&data)
+}
+
+// Live (not removed or played) song indexed under the key
+fn lookup_song_key(env: &impl Env, dj_address: [u8; 20], set_id: u32, dedup_key: &[u8; 32]) -> Option<u32> {
+    let mut song_id = match get_u32(env, &get_song_index_key(env, &dj_address, set_id, dedup_key)) {
+        0 => return None,
+        id_plus_one => id_plus_one - 1,
+    };
+    // A merged entry stands for the song it was merged into
+    while let Some(into_id) = get_merged_into(env, dj_address, set_id, song_id) {
+        song_id = into_id;
+    }
+    if is_song_removed(env, dj_address, set_id, song_id) || get_played_at(env, dj_address, set_id, song_id) != 0 {
+        return None;
+    }
+    Some(song_id)
+}
+
+// The existing entry for the key, or DuplicateSong when the DJ rejects duplicates
+fn find_duplicate(env: &impl Env, dj_address: [u8; 20], set_id: u32, dedup_key: &[u8; 32]) -> Result<Option<u32>, Error> {
+    match lookup_song_key(env, dj_address, set_id, dedup_key) {
+        Some(_) if rejects_duplicates(env, dj_address) => Err(Error::DuplicateSong),
+        existing => Ok(existing),
+    }
+}
+
+fn set_reject_duplicates(env: &mut impl Env, reject: bool) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_dj(env, origin), Error::NotDj);
+    
+    save_bool(env, &get_reject_duplicates_key(&origin), reject);
+    
+    emit_event(
+        env,
+        &[EVENT_REJECT_DUPLICATES_SET, address_topic(&origin)],
+        &[Token::Bool(reject)],
+    );
+    
+    Ok(())
+}
+
+// Whether duplicates revert with DuplicateSong rather than resolving to the existing song
+fn rejects_duplicates(env: &impl Env, dj_address: [u8; 20]) -> bool {
+    get_bool(env, &get_reject_duplicates_key(&dj_address))
+}
+
+// Live song in the DJ's current set matching a name or source URI
+fn find_song(env: &impl Env, dj_address: [u8; 20], song_name: &[u8], source_uri: &[u8]) -> Option<u32> {
+    let set_id = get_current_set_id(env, dj_address);
+    lookup_song_key(env, dj_address, set_id, &normalized_song_key(env, song_name, source_uri))
+}
+
+// Suggestion limits: at most `max_per_set` suggestions per address in each set and at least
+// `min_interval` seconds between an address's suggestions; 0 switches either limit off
+fn set_suggestion_limits(env: &mut impl Env, max_per_set: u32, min_interval: u64) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_dj(env, origin), Error::NotDj);
+    
+    save_u32(env, &get_suggestion_limits_key(&origin, 0), max_per_set);
+    save_u64(env, &get_suggestion_limits_key(&origin, 1), min_interval);
+    
+    emit_event(
+        env,
+        &[EVENT_SUGGESTION_LIMITS_SET, address_topic(&origin)],
+        &[Token::Uint(max_per_set.into()), Token::Uint(min_interval.into())],
+    );
+    
+    Ok(())
+}
+
+// (max_per_set, min_interval)
+fn get_suggestion_limits(env: &impl Env, dj_address: [u8; 20]) -> (u32, u64) {
+    (
+        get_u32(env, &get_suggestion_limits_key(&dj_address, 0)),
+        get_u64(env, &get_suggestion_limits_key(&dj_address, 1)),
+    )
+}
+
+// Suggestions an address has left in the set (None when unlimited) and the earliest time
+// it may suggest again. The count and last time come from the suggester's song index
+fn get_suggestion_allowance(env: &impl Env, dj_address: [u8; 20], set_id: u32, suggester: [u8; 20]) -> (Option<u32>, u64) {
+    let (max_per_set, min_interval) = get_suggestion_limits(env, dj_address);
+    let count = get_u32(env, &get_suggester_song_count_key(env, &dj_address, set_id, &suggester));
+    
+    let remaining = match max_per_set {
+        0 => None,
+        max => Some(max.saturating_sub(count)),
+    };
+    
+    let next_allowed_at = if min_interval == 0 || count == 0 {
+        0
+    } else {
+        let last_song_id = get_u32(env, &get_suggester_song_key(env, &dj_address, set_id, &suggester, count - 1));
+        let last_at = get_u64(env, &get_song_attribute_key(PREFIX_SONG_ADDED_AT, &dj_address, set_id, last_song_id));
+        last_at.saturating_add(min_interval)
+    };
+    
+    (remaining, next_allowed_at)
+}
+
+// Records who added a song and when
+fn save_song_origin(env: &mut impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) {
+    let origin = env.caller();
+    let now = env.now();
+    save_address(env, &get_song_attribute_key(PREFIX_SONG_ADDED_BY, &dj_address, set_id, song_id), &origin);
+    save_u64(env, &get_song_attribute_key(PREFIX_SONG_ADDED_AT, &dj_address, set_id, song_id), now);
+}
+
+// Who added a song, when, and whether it came in as a suggestion rather than through `add_song`
+fn get_song_origin(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> Result<([u8; 20], u64, bool), Error> {
+    ensure!(song_id < get_song_count(env, dj_address, set_id), Error::SongNotFound);
+    
+    let added_by = get_address(env, &get_song_attribute_key(PREFIX_SONG_ADDED_BY, &dj_address, set_id, song_id)).unwrap_or([0u8; 20]);
+    let added_at = get_u64(env, &get_song_attribute_key(PREFIX_SONG_ADDED_AT, &dj_address, set_id, song_id));
+    let suggested = get_bool(env, &get_song_attribute_key(PREFIX_SONG_SUGGESTED, &dj_address, set_id, song_id));
+    Ok((added_by, added_at, suggested))
+}
+
+// IDs of the songs an address suggested to a DJ's set, oldest first
+fn get_songs_by_suggester(env: &impl Env, dj_address: [u8; 20], set_id: u32, suggester: [u8; 20]) -> Vec<u32> {
+    let count = get_u32(env, &get_suggester_song_count_key(env, &dj_address, set_id, &suggester));
+    let mut song_ids = Vec::new();
+    
+    for i in 0..count {
+        song_ids.push(get_u32(env, &get_suggester_song_key(env, &dj_address, set_id, &suggester, i)));
+    }
+    
+    song_ids
+}
+
+// Suggestion approval: when a DJ turns it on, new suggestions wait outside the
+// votable queue until the DJ or a delegate approves or rejects them
+fn set_suggestion_approval(env: &mut impl Env, required: bool) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_dj(env, origin), Error::NotDj);
+    
+    save_bool(env, &get_suggestion_approval_key(&origin), required);
+    
+    emit_event(
+        env,
+        &[EVENT_SUGGESTION_APPROVAL_SET, address_topic(&origin)],
+        &[Token::Bool(required)],
+    );
+    
+    Ok(())
+}
+
+fn requires_suggestion_approval(env: &impl Env, dj_address: [u8; 20]) -> bool {
+    get_bool(env, &get_suggestion_approval_key(&dj_address))
+}
+
+fn is_song_pending(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> bool {
+    get_bool(env, &get_song_attribute_key(PREFIX_SONG_PENDING, &dj_address, set_id, song_id))
+}
+
+// Moves a pending suggestion into the queue, or drops it as removed when `approve` is false
+fn review_suggestion(env: &mut impl Env, dj_address: [u8; 20], song_id: u32, approve: bool) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(can_act_for(env, &origin, dj_address, PERMISSION_APPROVE_SUGGESTION), Error::Unauthorized);
+    
+    let set_id = get_current_set_id(env, dj_address);
+    ensure!(song_id < get_song_count(env, dj_address, set_id), Error::SongNotFound);
+    ensure!(is_song_pending(env, dj_address, set_id, song_id), Error::NotPending);
+    
+    save_bool(env, &get_song_attribute_key(PREFIX_SONG_PENDING, &dj_address, set_id, song_id), false);
     
-    save_u32(&count_key, song_id + 1);
+    let event = if approve {
+        EVENT_SUGGESTION_APPROVED
+    } else {
+        save_bool(env, &get_song_removed_key(&dj_address, set_id, song_id), true);
+        EVENT_SUGGESTION_REJECTED
+    };
+    emit_event(env, &[event, address_topic(&dj_address), u32_topic(song_id), address_topic(&origin)], &[]);
     
-    song_id
+    Ok(())
 }
 
-fn remove_song(song_id: u32) {
-    let origin = get_origin();
+// Suggestions of the set still awaiting review
+fn get_pending_suggestions(env: &impl Env, dj_address: [u8; 20], set_id: u32) -> Vec<(u32, Vec<u8>, u32)> {
+    let song_count = get_song_count(env, dj_address, set_id);
+    let mut songs = Vec::new();
+    
+    for i in 0..song_count {
+        if is_song_pending(env, dj_address, set_id, i) && !is_song_removed(env, dj_address, set_id, i) {
+            songs.push((i, get_song(env, dj_address, set_id, i), get_votes(env, dj_address, set_id, i)));
+        }
+    }
+    
+    songs
+}
+
+// Structured songs: title, artist, optional source URI and duration in seconds. The
+// "Artist - Title" display name is still stored as the song string, so the string API
+// and every existing view keep working unchanged
+fn add_song_detailed(env: &mut impl Env, title: Vec<u8>, artist: Vec<u8>, source_uri: Vec<u8>, duration: u32) -> Result<u32, Error> {
+    let song_name = song_display_name(&title, &artist, &source_uri)?;
+    let (song_id, created) = add_song_entry(env, song_name, &source_uri)?;
+    
+    if created {
+        let origin = env.caller();
+        let set_id = get_current_set_id(env, origin);
+        save_song_details(env, origin, set_id, song_id, &title, &artist, &source_uri, duration);
+    }
+    
+    Ok(song_id)
+}
+
+fn suggest_song_detailed(env: &mut impl Env, dj_address: [u8; 20], title: Vec<u8>, artist: Vec<u8>, source_uri: Vec<u8>, duration: u32) -> Result<u32, Error> {
+    let song_name = song_display_name(&title, &artist, &source_uri)?;
+    let (song_id, created) = suggest_song_entry(env, dj_address, song_name, &source_uri)?;
+    
+    if created {
+        let set_id = get_current_set_id(env, dj_address);
+        save_song_details(env, dj_address, set_id, song_id, &title, &artist, &source_uri, duration);
+    }
     
-    assert!(get_bool(&get_dj_key(&origin)), "NOT_DJ");
+    Ok(song_id)
+}
+
+// Validates the structured fields and builds the display name
+fn song_display_name(title: &[u8], artist: &[u8], source_uri: &[u8]) -> Result<Vec<u8>, Error> {
+    ensure!(!title.is_empty(), Error::EmptySongName);
+    ensure!(title.len() <= MAX_SONG_TITLE_LEN, Error::SongNameTooLong);
+    ensure!(artist.len() <= MAX_SONG_ARTIST_LEN, Error::SongNameTooLong);
+    ensure!(source_uri.len() <= MAX_SOURCE_URI_LEN, Error::SourceUriTooLong);
+    
+    if artist.is_empty() {
+        return Ok(title.to_vec());
+    }
+    let mut name = artist.to_vec();
+    name.extend_from_slice(b" - ");
+    name.extend_from_slice(title);
+    Ok(name)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_song_details(env: &mut impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32, title: &[u8], artist: &[u8], source_uri: &[u8], duration: u32) {
+    save_string(env, &get_song_attribute_key(PREFIX_SONG_TITLE, &dj_address, set_id, song_id), title);
+    save_string(env, &get_song_attribute_key(PREFIX_SONG_ARTIST, &dj_address, set_id, song_id), artist);
+    save_string(env, &get_song_attribute_key(PREFIX_SONG_SOURCE_URI, &dj_address, set_id, song_id), source_uri);
+    save_u32(env, &get_song_attribute_key(PREFIX_SONG_DURATION, &dj_address, set_id, song_id), duration);
+}
+
+// (title, artist, source_uri, duration, added_by, added_at)
+type SongDetails = (Vec<u8>, Vec<u8>, Vec<u8>, u32, [u8; 20], u64);
+
+// Songs added through the string API report their whole name as the title; songs
+// from before origins were recorded report the zero address and time 0
+fn get_song_details(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> Result<SongDetails, Error> {
+    let song_name = get_string(env, &get_song_key(&dj_address, set_id, song_id), MAX_SONG_NAME_LEN);
+    let song_name = song_name.ok_or(Error::SongNotFound)?;
+    
+    let title_key = get_song_attribute_key(PREFIX_SONG_TITLE, &dj_address, set_id, song_id);
+    let (title, artist, source_uri, duration) = match get_string(env, &title_key, MAX_SONG_TITLE_LEN) {
+        Some(title) => (
+            title,
+            get_string(env, &get_song_attribute_key(PREFIX_SONG_ARTIST, &dj_address, set_id, song_id), MAX_SONG_ARTIST_LEN).unwrap_or_default(),
+            get_string(env, &get_song_attribute_key(PREFIX_SONG_SOURCE_URI, &dj_address, set_id, song_id), MAX_SOURCE_URI_LEN).unwrap_or_default(),
+            get_u32(env, &get_song_attribute_key(PREFIX_SONG_DURATION, &dj_address, set_id, song_id)),
+        ),
+        None => (song_name, Vec::new(), Vec::new(), 0),
+    };
+    let added_by = get_address(env, &get_song_attribute_key(PREFIX_SONG_ADDED_BY, &dj_address, set_id, song_id)).unwrap_or([0u8; 20]);
+    let added_at = get_u64(env, &get_song_attribute_key(PREFIX_SONG_ADDED_AT, &dj_address, set_id, song_id));
+    
+    Ok((title, artist, source_uri, duration, added_by, added_at))
+}
+
+fn remove_song(env: &mut impl Env, song_id: u32) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(get_bool(env, &get_dj_key(&origin)), Error::NotDj);
     
-    let song_key = get_song_key(&origin, song_id);
-    assert!(get_string(&song_key).is_some(), "SONG_NOT_FOUND");
+    let set_id = get_current_set_id(env, origin);
+    let song_key = get_song_key(&origin, set_id, song_id);
+    ensure!(get_string(env, &song_key, MAX_SONG_NAME_LEN).is_some(), Error::SongNotFound);
     
     // Mark song as removed
-    let removed_key = get_song_removed_key(&origin, song_id);
-    save_bool(&removed_key, true);
+    let removed_key = get_song_removed_key(&origin, set_id, song_id);
+    save_bool(env, &removed_key, true);
+    
+    emit_event(env, &[EVENT_SONG_REMOVED, address_topic(&origin), u32_topic(song_id), address_topic(&origin)], &[]);
     
     // Don't clear votes - preserve them for historical purposes
-    // let votes_key = get_votes_key(&origin, song_id);
-    // save_u32(&votes_key, 0);
+    // let votes_key = get_votes_key(&origin, set_id, song_id);
+    // save_u32(env, &votes_key, 0);
+    
+    Ok(())
+}
+
+// Remove a song from a DJ's queue on their behalf
+fn remove_song_for(env: &mut impl Env, dj_address: [u8; 20], song_id: u32) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(can_act_for(env, &origin, dj_address, PERMISSION_REMOVE_SONG), Error::Unauthorized);
+    ensure!(is_dj(env, dj_address), Error::TargetNotDj);
+    
+    let set_id = get_current_set_id(env, dj_address);
+    let song_key = get_song_key(&dj_address, set_id, song_id);
+    ensure!(get_string(env, &song_key, MAX_SONG_NAME_LEN).is_some(), Error::SongNotFound);
+    
+    let removed_key = get_song_removed_key(&dj_address, set_id, song_id);
+    save_bool(env, &removed_key, true);
+    
+    emit_event(env, &[EVENT_SONG_REMOVED, address_topic(&dj_address), u32_topic(song_id), address_topic(&origin)], &[]);
+    
+    Ok(())
 }
 
 // Universal remover function - any registered DJ can remove any song
-fn remove_song_universal(dj_address: [u8; 20], song_id: u32) {
-    let origin = get_origin();
+// Superseded by delegates and `remove_song_for`; the owner can switch it off for good
+fn remove_song_universal(env: &mut impl Env, dj_address: [u8; 20], song_id: u32) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(!is_universal_remover_retired(env), Error::UniversalRemoverIsRetired);
     
     // Only registered DJs can use this function
-    assert!(get_bool(&get_dj_key(&origin)), "NOT_DJ");
+    ensure!(get_bool(env, &get_dj_key(&origin)), Error::NotDj);
     
     // Check that the target DJ exists
-    assert!(get_bool(&get_dj_key(&dj_address)), "TARGET_NOT_DJ");
+    ensure!(get_bool(env, &get_dj_key(&dj_address)), Error::TargetNotDj);
     
     // Check that the song exists
-    let song_key = get_song_key(&dj_address, song_id);
-    assert!(get_string(&song_key).is_some(), "SONG_NOT_FOUND");
+    let set_id = get_current_set_id(env, dj_address);
+    let song_key = get_song_key(&dj_address, set_id, song_id);
+    ensure!(get_string(env, &song_key, MAX_SONG_NAME_LEN).is_some(), Error::SongNotFound);
     
     // Mark song as removed
-    let removed_key = get_song_removed_key(&dj_address, song_id);
-    save_bool(&removed_key, true);
+    let removed_key = get_song_removed_key(&dj_address, set_id, song_id);
+    save_bool(env, &removed_key, true);
+    
+    emit_event(env, &[EVENT_SONG_REMOVED, address_topic(&dj_address), u32_topic(song_id), address_topic(&origin)], &[]);
+    
+    Ok(())
+}
+
+fn retire_universal_remover(env: &mut impl Env) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_owner(env, &origin), Error::NotOwner);
+    
+    let retired_key = get_universal_remover_retired_key();
+    if !get_bool(env, &retired_key) {
+        save_bool(env, &retired_key, true);
+        
+        emit_event(env, &[EVENT_UNIVERSAL_REMOVER_RETIRED], &[]);
+    }
+    
+    Ok(())
+}
+
+fn is_universal_remover_retired(env: &impl Env) -> bool {
+    get_bool(env, &get_universal_remover_retired_key())
+}
+
+// Folds one entry of the DJ's current set into another: votes and voter flags move across,
+// a voter who backed both counts once, and the source is removed with a pointer to the target
+fn merge_songs(env: &mut impl Env, dj_address: [u8; 20], from_id: u32, into_id: u32) -> Result<u32, Error> {
+    let origin = env.caller();
+    
+    ensure!(can_act_for(env, &origin, dj_address, PERMISSION_REMOVE_SONG), Error::Unauthorized);
+    ensure!(from_id != into_id, Error::InvalidParams);
+    
+    let set_id = get_current_set_id(env, dj_address);
+    let song_count = get_song_count(env, dj_address, set_id);
+    for song_id in [from_id, into_id] {
+        ensure!(song_id < song_count, Error::SongNotFound);
+        ensure!(get_merged_into(env, dj_address, set_id, song_id).is_none(), Error::SongWasMerged);
+        ensure!(!is_song_removed(env, dj_address, set_id, song_id), Error::SongWasRemoved);
+        ensure!(get_played_at(env, dj_address, set_id, song_id) == 0, Error::SongAlreadyPlayed);
+        ensure!(!is_song_pending(env, dj_address, set_id, song_id), Error::SongPending);
+    }
+    
+    let from_votes = get_votes(env, dj_address, set_id, from_id);
+    let mut tracked_votes = 0u32;
+    let mut moved_votes = 0u32;
+    let mut moved_downvotes = 0u32;
+    for voter in get_song_voters(env, dj_address, set_id, from_id) {
+        let upvoted = has_voted(env, voter, dj_address, set_id, from_id);
+        let downvoted = has_downvoted(env, voter, dj_address, set_id, from_id);
+        if !upvoted && !downvoted {
+            continue;
+        }
+        let stored_votes = get_u32(env, &get_votes_cast_key(env, &voter, &dj_address, set_id, from_id));
+        let votes_cast = get_votes_cast(env, voter, dj_address, set_id, from_id);
+        let downvote_weight = get_downvote_weight(env, voter, dj_address, set_id, from_id);
+        let voted_at = get_u64(env, &get_voted_at_key(env, &voter, &dj_address, set_id, from_id));
+        save_bool(env, &get_has_voted_key(env, &voter, &dj_address, set_id, from_id), false);
+        save_bool(env, &get_has_downvoted_key(env, &voter, &dj_address, set_id, from_id), false);
+        save_u32(env, &get_votes_cast_key(env, &voter, &dj_address, set_id, from_id), 0);
+        save_u32(env, &get_downvote_weight_key(env, &voter, &dj_address, set_id, from_id), 0);
+        
+        // A voter who already voted on the target either way keeps that vote only,
+        // and the one they cast on the source goes back to their budget or credits
+        let into_voted = has_voted(env, voter, dj_address, set_id, into_id)
+            || has_downvoted(env, voter, dj_address, set_id, into_id);
+        if upvoted {
+            tracked_votes = tracked_votes.saturating_add(votes_cast);
+        }
+        if into_voted {
+            if is_quadratic_voting(env, dj_address) {
+                refund_credits(env, voter, dj_address, set_id, u64::from(stored_votes).pow(2));
+            } else {
+                refund_vote(env, voter, dj_address, set_id);
+            }
+            continue;
+        }
+        
+        if upvoted {
+            save_bool(env, &get_has_voted_key(env, &voter, &dj_address, set_id, into_id), true);
+            save_u32(env, &get_votes_cast_key(env, &voter, &dj_address, set_id, into_id), stored_votes);
+            moved_votes = moved_votes.saturating_add(votes_cast);
+        } else {
+            save_bool(env, &get_has_downvoted_key(env, &voter, &dj_address, set_id, into_id), true);
+            save_u32(env, &get_downvote_weight_key(env, &voter, &dj_address, set_id, into_id), downvote_weight);
+            moved_downvotes = moved_downvotes.saturating_add(downvote_weight);
+        }
+        save_u64(env, &get_voted_at_key(env, &voter, &dj_address, set_id, into_id), voted_at);
+        record_voter(env, voter, dj_address, set_id, into_id);
+    }
+    // Votes cast before voters were listed can't be matched up, so they move as they are
+    moved_votes += from_votes.saturating_sub(tracked_votes);
+    
+    let into_votes_key = get_votes_key(&dj_address, set_id, into_id);
+    let into_votes = get_u32(env, &into_votes_key);
+    save_u32(env, &into_votes_key, into_votes.saturating_add(moved_votes));
+    save_u32(env, &get_votes_key(&dj_address, set_id, from_id), 0);
+    
+    let into_downvotes_key = get_song_attribute_key(PREFIX_DOWNVOTES, &dj_address, set_id, into_id);
+    let into_downvotes = get_u32(env, &into_downvotes_key);
+    save_u32(env, &into_downvotes_key, into_downvotes.saturating_add(moved_downvotes));
+    save_u32(env, &get_song_attribute_key(PREFIX_DOWNVOTES, &dj_address, set_id, from_id), 0);
+    
+    save_bool(env, &get_song_removed_key(&dj_address, set_id, from_id), true);
+    save_u32(env, &get_song_attribute_key(PREFIX_SONG_MERGED_INTO, &dj_address, set_id, from_id), into_id + 1);
+    
+    emit_event(
+        env,
+        &[EVENT_SONGS_MERGED, address_topic(&dj_address), u32_topic(from_id), u32_topic(into_id)],
+        &[Token::Uint(moved_votes.into())],
+    );
+    
+    Ok(into_votes.saturating_add(moved_votes))
+}
+
+// Target a song was merged into, if it was
+fn get_merged_into(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> Option<u32> {
+    match get_u32(env, &get_song_attribute_key(PREFIX_SONG_MERGED_INTO, &dj_address, set_id, song_id)) {
+        0 => None,
+        id_plus_one => Some(id_plus_one - 1),
+    }
 }
 
-fn is_song_removed(dj_address: [u8; 20], song_id: u32) -> bool {
-    let removed_key = get_song_removed_key(&dj_address, song_id);
-    get_bool(&removed_key)
+fn is_song_removed(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> bool {
+    let removed_key = get_song_removed_key(&dj_address, set_id, song_id);
+    get_bool(env, &removed_key)
 }
 
-fn get_song(dj_address: [u8; 20], song_id: u32) -> Vec<u8> {
-    let song_key = get_song_key(&dj_address, song_id);
-    get_string(&song_key).unwrap_or_else(|| vec![])
+fn get_song(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> Vec<u8> {
+    let song_key = get_song_key(&dj_address, set_id, song_id);
+    get_string(env, &song_key, MAX_SONG_NAME_LEN).unwrap_or_default()
 }
 
-fn get_song_count(dj_address: [u8; 20]) -> u32 {
-    let count_key = get_song_count_key(&dj_address);
-    get_u32(&count_key)
+fn get_song_count(env: &impl Env, dj_address: [u8; 20], set_id: u32) -> u32 {
+    let count_key = get_song_count_key(&dj_address, set_id);
+    get_u32(env, &count_key)
 }
 
-fn vote(dj_address: [u8; 20], song_id: u32) {
-    let voter = get_origin();
+fn vote(env: &mut impl Env, dj_address: [u8; 20], song_id: u32) -> Result<(), Error> {
+    let voter = env.caller();
     
     // Check if the DJ's set is currently active
-    assert!(is_set_active(dj_address), "SET_NOT_ACTIVE");
+    ensure!(is_set_active(env, dj_address), Error::SetNotActive);
+    ensure!(!is_quadratic_voting(env, dj_address), Error::WrongVotingMode);
+    ensure!(holds_ticket(env, dj_address, voter)?, Error::NotTicketHolder);
+    let set_id = get_current_set_id(env, dj_address);
     
     // Check if song exists
-    let song_key = get_song_key(&dj_address, song_id);
-    assert!(get_string(&song_key).is_some(), "SONG_NOT_FOUND");
+    let song_key = get_song_key(&dj_address, set_id, song_id);
+    ensure!(get_string(env, &song_key, MAX_SONG_NAME_LEN).is_some(), Error::SongNotFound);
+    
+    // Check if song is merged, removed, already played or still awaiting approval
+    ensure!(get_merged_into(env, dj_address, set_id, song_id).is_none(), Error::SongWasMerged);
+    ensure!(!is_song_removed(env, dj_address, set_id, song_id), Error::SongWasRemoved);
+    ensure!(get_played_at(env, dj_address, set_id, song_id) == 0, Error::SongAlreadyPlayed);
+    ensure!(!is_song_pending(env, dj_address, set_id, song_id), Error::SongPending);
+    
+    let has_voted_key = get_has_voted_key(env, &voter, &dj_address, set_id, song_id);
+    ensure!(!get_bool(env, &has_voted_key), Error::AlreadyVoted);
+    ensure!(!has_downvoted(env, voter, dj_address, set_id, song_id), Error::ConflictingVote);
+    
+    // Weight is fixed when the vote is cast so unvoting takes back exactly as much
+    let weight = get_vote_weight(env, voter, dj_address)?;
+    ensure!(weight > 0, Error::NoVotingWeight);
+    
+    // Spend one vote from the voter's budget for this set
+    ensure!(get_remaining_votes(env, voter, dj_address, set_id) != Some(0), Error::VoteBudgetExceeded);
+    let spent_key = get_votes_spent_key(env, &voter, &dj_address, set_id);
+    let spent = get_u32(env, &spent_key);
+    save_u32(env, &spent_key, spent + 1);
     
-    // Check if song is removed
-    assert!(!is_song_removed(dj_address, song_id), "SONG_REMOVED");
+    save_bool(env, &has_voted_key, true);
+    save_u32(env, &get_votes_cast_key(env, &voter, &dj_address, set_id, song_id), weight);
+    save_u64(env, &get_voted_at_key(env, &voter, &dj_address, set_id, song_id), env.now());
+    record_voter(env, voter, dj_address, set_id, song_id);
     
-    let has_voted_key = get_has_voted_key(&voter, &dj_address, song_id);
-    assert!(!get_bool(&has_voted_key), "ALREADY_VOTED");
+    let votes_key = get_votes_key(&dj_address, set_id, song_id);
+    let new_votes = get_u32(env, &votes_key).saturating_add(weight);
+    save_u32(env, &votes_key, new_votes);
     
-    save_bool(&has_voted_key, true);
+    emit_event(
+        env,
+        &[EVENT_VOTED, address_topic(&dj_address), u32_topic(song_id), address_topic(&voter)],
+        &[Token::Uint(new_votes.into())],
+    );
     
-    let votes_key = get_votes_key(&dj_address, song_id);
-    let current_votes = get_u32(&votes_key);
-    save_u32(&votes_key, current_votes + 1);
+    Ok(())
 }
 
-fn unvote(dj_address: [u8; 20], song_id: u32) {
-    let voter = get_origin();
+// Downvotes mirror votes with their own tally and per-voter flags, and spend from the
+// same budget. A voter can back a song or push it down, not both
+fn downvote(env: &mut impl Env, dj_address: [u8; 20], song_id: u32) -> Result<(), Error> {
+    let voter = env.caller();
     
-    // Check if the DJ's set is currently active
-    assert!(is_set_active(dj_address), "SET_NOT_ACTIVE");
+    ensure!(is_set_active(env, dj_address), Error::SetNotActive);
+    ensure!(!is_quadratic_voting(env, dj_address), Error::WrongVotingMode);
+    ensure!(holds_ticket(env, dj_address, voter)?, Error::NotTicketHolder);
+    let set_id = get_current_set_id(env, dj_address);
     
-    // Check if song exists
-    let song_key = get_song_key(&dj_address, song_id);
-    assert!(get_string(&song_key).is_some(), "SONG_NOT_FOUND");
+    ensure!(song_id < get_song_count(env, dj_address, set_id), Error::SongNotFound);
+    ensure!(get_merged_into(env, dj_address, set_id, song_id).is_none(), Error::SongWasMerged);
+    ensure!(!is_song_removed(env, dj_address, set_id, song_id), Error::SongWasRemoved);
+    ensure!(get_played_at(env, dj_address, set_id, song_id) == 0, Error::SongAlreadyPlayed);
+    ensure!(!is_song_pending(env, dj_address, set_id, song_id), Error::SongPending);
     
-    // Check if the user has actually voted
-    let has_voted_key = get_has_voted_key(&voter, &dj_address, song_id);
-    assert!(get_bool(&has_voted_key), "NOT_VOTED");
+    let has_downvoted_key = get_has_downvoted_key(env, &voter, &dj_address, set_id, song_id);
+    ensure!(!get_bool(env, &has_downvoted_key), Error::AlreadyVoted);
+    ensure!(!has_voted(env, voter, dj_address, set_id, song_id), Error::ConflictingVote);
     
-    // Remove the vote record
-    save_bool(&has_voted_key, false);
+    // Weighted like upvotes, and fixed at cast time the same way
+    let weight = get_vote_weight(env, voter, dj_address)?;
+    ensure!(weight > 0, Error::NoVotingWeight);
     
-    // Decrease the vote count
-    let votes_key = get_votes_key(&dj_address, song_id);
-    let current_votes = get_u32(&votes_key);
-    if current_votes > 0 {
-        save_u32(&votes_key, current_votes - 1);
-    }
+    ensure!(get_remaining_votes(env, voter, dj_address, set_id) != Some(0), Error::VoteBudgetExceeded);
+    let spent_key = get_votes_spent_key(env, &voter, &dj_address, set_id);
+    let spent = get_u32(env, &spent_key);
+    save_u32(env, &spent_key, spent + 1);
+    
+    save_bool(env, &has_downvoted_key, true);
+    save_u32(env, &get_downvote_weight_key(env, &voter, &dj_address, set_id, song_id), weight);
+    save_u64(env, &get_voted_at_key(env, &voter, &dj_address, set_id, song_id), env.now());
+    record_voter(env, voter, dj_address, set_id, song_id);
+    
+    let downvotes_key = get_song_attribute_key(PREFIX_DOWNVOTES, &dj_address, set_id, song_id);
+    let downvotes = get_u32(env, &downvotes_key).saturating_add(weight);
+    save_u32(env, &downvotes_key, downvotes);
+    
+    emit_event(
+        env,
+        &[EVENT_DOWNVOTED, address_topic(&dj_address), u32_topic(song_id), address_topic(&voter)],
+        &[Token::Uint(downvotes.into())],
+    );
+    
+    Ok(())
+}
+
+fn undownvote(env: &mut impl Env, dj_address: [u8; 20], song_id: u32) -> Result<(), Error> {
+    let voter = env.caller();
+    
+    ensure!(is_set_active(env, dj_address), Error::SetNotActive);
+    ensure!(!is_quadratic_voting(env, dj_address), Error::WrongVotingMode);
+    ensure!(holds_ticket(env, dj_address, voter)?, Error::NotTicketHolder);
+    let set_id = get_current_set_id(env, dj_address);
+    
+    ensure!(song_id < get_song_count(env, dj_address, set_id), Error::SongNotFound);
+    
+    let has_downvoted_key = get_has_downvoted_key(env, &voter, &dj_address, set_id, song_id);
+    ensure!(get_bool(env, &has_downvoted_key), Error::NotVoted);
+    
+    let weight = get_downvote_weight(env, voter, dj_address, set_id, song_id);
+    save_bool(env, &has_downvoted_key, false);
+    save_u32(env, &get_downvote_weight_key(env, &voter, &dj_address, set_id, song_id), 0);
+    refund_vote(env, voter, dj_address, set_id);
+    
+    let downvotes_key = get_song_attribute_key(PREFIX_DOWNVOTES, &dj_address, set_id, song_id);
+    let downvotes = get_u32(env, &downvotes_key).saturating_sub(weight);
+    save_u32(env, &downvotes_key, downvotes);
+    
+    emit_event(
+        env,
+        &[EVENT_UNDOWNVOTED, address_topic(&dj_address), u32_topic(song_id), address_topic(&voter)],
+        &[Token::Uint(downvotes.into())],
+    );
+    
+    Ok(())
+}
+
+fn has_downvoted(env: &impl Env, voter: [u8; 20], dj_address: [u8; 20], set_id: u32, song_id: u32) -> bool {
+    get_bool(env, &get_has_downvoted_key(env, &voter, &dj_address, set_id, song_id))
+}
+
+// Weight the voter's downvote on the song was cast with; 0 without one
+fn get_downvote_weight(env: &impl Env, voter: [u8; 20], dj_address: [u8; 20], set_id: u32, song_id: u32) -> u32 {
+    match get_u32(env, &get_downvote_weight_key(env, &voter, &dj_address, set_id, song_id)) {
+        0 => has_downvoted(env, voter, dj_address, set_id, song_id) as u32,
+        weight => weight,
+    }
+}
+
+fn get_downvotes(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> u32 {
+    get_u32(env, &get_song_attribute_key(PREFIX_DOWNVOTES, &dj_address, set_id, song_id))
+}
+
+// Upvotes minus downvotes
+fn get_net_score(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> i64 {
+    i64::from(get_votes(env, dj_address, set_id, song_id)) - i64::from(get_downvotes(env, dj_address, set_id, song_id))
+}
+
+// Vote decay: with a half-life set, a vote's weight halves every `half_life` seconds
+// after it was cast and the queue ranks by the decayed score; 0 turns it off
+fn set_vote_decay(env: &mut impl Env, half_life: u64) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_dj(env, origin), Error::NotDj);
+    
+    save_u64(env, &get_vote_decay_key(&origin), half_life);
+    
+    emit_event(env, &[EVENT_VOTE_DECAY_SET, address_topic(&origin)], &[Token::Uint(half_life.into())]);
+    
+    Ok(())
+}
+
+fn get_vote_decay(env: &impl Env, dj_address: [u8; 20]) -> u64 {
+    get_u64(env, &get_vote_decay_key(&dj_address))
+}
+
+// Weight of a vote `age` seconds old in SCORE_SCALE units: whole half-lives shift it
+// down, the rest is interpolated between sixteenth steps of HALF_LIFE_STEPS
+fn decay_weight(age: u64, half_life: u64) -> u64 {
+    let halvings = age / half_life;
+    if halvings >= 64 {
+        return 0;
+    }
+    let position = u128::from(age % half_life) * 16;
+    let step = (position / u128::from(half_life)) as usize;
+    let fraction = position % u128::from(half_life);
+    let (upper, lower) = (HALF_LIFE_STEPS[step], HALF_LIFE_STEPS[step + 1]);
+    let weight = upper - (u128::from(upper - lower) * fraction / u128::from(half_life)) as u64;
+    weight >> halvings
+}
+
+// Net score with each vote weighted by its age, in SCORE_SCALE units
+fn get_decayed_score(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32, half_life: u64) -> i64 {
+    let now = env.now();
+    let set_start = get_u64(env, &get_set_start_time_key(&dj_address, set_id));
+    let cleared_at = get_u64(env, &get_song_attribute_key(PREFIX_VOTES_CLEARED_AT, &dj_address, set_id, song_id));
+    let mut score = 0i64;
+    let mut tracked_votes = 0u32;
+    
+    for voter in get_song_voters(env, dj_address, set_id, song_id) {
+        let voted_at = get_u64(env, &get_voted_at_key(env, &voter, &dj_address, set_id, song_id));
+        // Votes cast in the block that cleared them count, so a same-block revote isn't lost
+        if voted_at < cleared_at {
+            continue;
+        }
+        let weight = decay_weight(now.saturating_sub(voted_at), half_life) as i64;
+        if has_voted(env, voter, dj_address, set_id, song_id) {
+            let votes = get_votes_cast(env, voter, dj_address, set_id, song_id);
+            tracked_votes += votes;
+            score += weight * i64::from(votes);
+        } else if has_downvoted(env, voter, dj_address, set_id, song_id) {
+            score -= weight * i64::from(get_downvote_weight(env, voter, dj_address, set_id, song_id));
+        }
+    }
+    
+    // Votes cast before voters were listed have no timestamp, so they age from the set start
+    let untracked_votes = get_votes(env, dj_address, set_id, song_id).saturating_sub(tracked_votes);
+    score + decay_weight(now.saturating_sub(set_start), half_life) as i64 * i64::from(untracked_votes)
+}
+
+// Score the queue is ranked by: the decayed score when the DJ has a half-life set,
+// otherwise the net score, both in SCORE_SCALE units
+fn get_ranking_score(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> i64 {
+    match get_vote_decay(env, dj_address) {
+        0 => get_net_score(env, dj_address, set_id, song_id) * SCORE_SCALE as i64,
+        half_life => get_decayed_score(env, dj_address, set_id, song_id, half_life),
+    }
+}
+
+// Auto-hide: when on, songs whose net score drops below the threshold leave the queue
+// views until upvotes lift them back; they can still be voted on
+fn set_auto_hide(env: &mut impl Env, enabled: bool, threshold: i64) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_dj(env, origin), Error::NotDj);
+    
+    save_bool(env, &get_auto_hide_key(&origin, 0), enabled);
+    save_u64(env, &get_auto_hide_key(&origin, 1), threshold as u64);
+    
+    emit_event(
+        env,
+        &[EVENT_AUTO_HIDE_SET, address_topic(&origin)],
+        &[Token::Bool(enabled), i64_to_token(threshold)],
+    );
+    
+    Ok(())
+}
+
+// (enabled, threshold)
+fn get_auto_hide(env: &impl Env, dj_address: [u8; 20]) -> (bool, i64) {
+    (
+        get_bool(env, &get_auto_hide_key(&dj_address, 0)),
+        get_u64(env, &get_auto_hide_key(&dj_address, 1)) as i64,
+    )
+}
+
+fn is_song_hidden(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> bool {
+    let (enabled, threshold) = get_auto_hide(env, dj_address);
+    enabled && get_net_score(env, dj_address, set_id, song_id) < threshold
+}
+
+// Songs still up for play as (song_id, name, upvotes, downvotes, net_score, hidden),
+// auto-hidden ones included and flagged
+fn get_songs_with_scores(env: &impl Env, dj_address: [u8; 20], set_id: u32) -> Vec<(u32, Vec<u8>, u32, u32, i64, bool)> {
+    let song_count = get_song_count(env, dj_address, set_id);
+    let mut songs = Vec::new();
+    
+    for i in 0..song_count {
+        if is_song_removed(env, dj_address, set_id, i)
+            || get_played_at(env, dj_address, set_id, i) != 0
+            || is_song_pending(env, dj_address, set_id, i)
+        {
+            continue;
+        }
+        let upvotes = get_votes(env, dj_address, set_id, i);
+        let downvotes = get_downvotes(env, dj_address, set_id, i);
+        let net_score = i64::from(upvotes) - i64::from(downvotes);
+        let hidden = is_song_hidden(env, dj_address, set_id, i);
+        songs.push((i, get_song(env, dj_address, set_id, i), upvotes, downvotes, net_score, hidden));
+    }
+    
+    songs
+}
+
+// Appends the voter to the song's voter list the first time they vote on it. The list is
+// never pruned; the has-voted flags say which entries still hold a vote
+fn record_voter(env: &mut impl Env, voter: [u8; 20], dj_address: [u8; 20], set_id: u32, song_id: u32) {
+    let listed_key = get_song_voter_listed_key(env, &voter, &dj_address, set_id, song_id);
+    if get_bool(env, &listed_key) {
+        return;
+    }
+    save_bool(env, &listed_key, true);
+    
+    let count_key = get_song_attribute_key(PREFIX_SONG_VOTER_COUNT, &dj_address, set_id, song_id);
+    let count = get_u32(env, &count_key);
+    save_address(env, &get_song_voter_key(env, &dj_address, set_id, song_id, count), &voter);
+    save_u32(env, &count_key, count + 1);
+}
+
+// Everyone who has voted or downvoted on the song, including ones who since took it back
+fn get_song_voters(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> Vec<[u8; 20]> {
+    let count = get_u32(env, &get_song_attribute_key(PREFIX_SONG_VOTER_COUNT, &dj_address, set_id, song_id));
+    let mut voters = Vec::new();
+    
+    for i in 0..count {
+        if let Some(voter) = get_address(env, &get_song_voter_key(env, &dj_address, set_id, song_id, i)) {
+            voters.push(voter);
+        }
+    }
+    
+    voters
+}
+
+
+// Vote budget: the most songs a voter can back in one of the DJ's sets; 0 means unlimited
+fn set_vote_budget(env: &mut impl Env, budget: u32) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_dj(env, origin), Error::NotDj);
+    
+    save_u32(env, &get_vote_budget_key(&origin), budget);
+    
+    emit_event(env, &[EVENT_VOTE_BUDGET_SET, address_topic(&origin)], &[Token::Uint(budget.into())]);
+    
+    Ok(())
+}
+
+fn get_vote_budget(env: &impl Env, dj_address: [u8; 20]) -> u32 {
+    get_u32(env, &get_vote_budget_key(&dj_address))
+}
+
+// Votes left for the voter in the set, or None when the DJ sets no budget
+fn get_remaining_votes(env: &impl Env, voter: [u8; 20], dj_address: [u8; 20], set_id: u32) -> Option<u32> {
+    match get_vote_budget(env, dj_address) {
+        0 => None,
+        budget => Some(budget.saturating_sub(get_u32(env, &get_votes_spent_key(env, &voter, &dj_address, set_id)))),
+    }
+}
+
+fn refund_vote(env: &mut impl Env, voter: [u8; 20], dj_address: [u8; 20], set_id: u32) {
+    let spent_key = get_votes_spent_key(env, &voter, &dj_address, set_id);
+    let spent = get_u32(env, &spent_key);
+    save_u32(env, &spent_key, spent.saturating_sub(1));
+}
+
+// Token-weighted votes: with a vote token set, `vote` weighs each vote by the voter's
+// balance of it, counted in `unit`s and run through the curve (square root, or capped
+// at `cap`). The zero address turns weighting off
+fn set_vote_token(env: &mut impl Env, token: [u8; 20], curve: u32, unit: ethabi::Uint, cap: u32) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_dj(env, origin), Error::NotDj);
+    ensure!(curve <= WEIGHT_CURVE_CAPPED, Error::InvalidParams);
+    ensure!(token == [0u8; 20] || !unit.is_zero(), Error::InvalidParams);
+    ensure!(curve != WEIGHT_CURVE_CAPPED || cap > 0, Error::InvalidParams);
+    
+    save_address(env, &get_vote_token_key(&origin, 0), &token);
+    save_u32(env, &get_vote_token_key(&origin, 1), curve);
+    save_uint(env, &get_vote_token_key(&origin, 2), unit);
+    save_u32(env, &get_vote_token_key(&origin, 3), cap);
+    
+    emit_event(
+        env,
+        &[EVENT_VOTE_TOKEN_SET, address_topic(&origin), address_topic(&token)],
+        &[Token::Uint(curve.into()), Token::Uint(unit), Token::Uint(cap.into())],
+    );
+    
+    Ok(())
+}
+
+// (token, curve, unit, cap); the token is None when votes are unweighted
+fn get_vote_token(env: &impl Env, dj_address: [u8; 20]) -> (Option<[u8; 20]>, u32, ethabi::Uint, u32) {
+    (
+        get_address(env, &get_vote_token_key(&dj_address, 0)).filter(|token| *token != [0u8; 20]),
+        get_u32(env, &get_vote_token_key(&dj_address, 1)),
+        get_uint(env, &get_vote_token_key(&dj_address, 2)),
+        get_u32(env, &get_vote_token_key(&dj_address, 3)),
+    )
+}
+
+// Weight a vote from `voter` would carry right now: 1 without a vote token
+fn get_vote_weight(env: &impl Env, voter: [u8; 20], dj_address: [u8; 20]) -> Result<u32, Error> {
+    let (token, curve, unit, cap) = get_vote_token(env, dj_address);
+    let Some(token) = token else {
+        return Ok(1);
+    };
+    
+    let units = get_token_balance(env, token, voter, None)? / unit;
+    let to_u32 = |weight: ethabi::Uint| if weight > u32::MAX.into() { u32::MAX } else { weight.as_u32() };
+    
+    Ok(match curve {
+        WEIGHT_CURVE_LINEAR => to_u32(units),
+        WEIGHT_CURVE_SQRT => to_u32(units.integer_sqrt()),
+        _ => to_u32(units).min(cap),
+    })
+}
+
+// `balanceOf(owner)` on a token contract, or `balanceOf(owner, id)` on an ERC-1155 one
+fn get_token_balance(env: &impl Env, token: [u8; 20], owner: [u8; 20], id: Option<ethabi::Uint>) -> Result<ethabi::Uint, Error> {
+    let input = match id {
+        None => [&ERC20_BALANCE_OF[..], &encode(&[Token::Address(owner.into())])].concat(),
+        Some(id) => [&ERC1155_BALANCE_OF[..], &encode(&[Token::Address(owner.into()), Token::Uint(id)])].concat(),
+    };
+    
+    let mut output = [0u8; 32];
+    ensure!(env.call_contract(&token, &input, &mut output) == Some(32), Error::TokenCallFailed);
+    
+    Ok(ethabi::Uint::from_big_endian(&output))
+}
+
+// Ticket gate: a DJ can require voters and suggesters to hold a token from an ERC-721
+// contract, or a given token ID of an ERC-1155 one; GATE_NONE opens the room again
+fn set_gate(env: &mut impl Env, kind: u32, token: [u8; 20], token_id: ethabi::Uint) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_dj(env, origin), Error::NotDj);
+    ensure!(kind <= GATE_ERC1155, Error::InvalidParams);
+    ensure!(kind == GATE_NONE || token != [0u8; 20], Error::InvalidParams);
+    
+    save_u32(env, &get_gate_key(&origin, 0), kind);
+    save_address(env, &get_gate_key(&origin, 1), &token);
+    save_uint(env, &get_gate_key(&origin, 2), token_id);
+    
+    emit_event(
+        env,
+        &[EVENT_GATE_SET, address_topic(&origin)],
+        &[Token::Uint(kind.into()), Token::Address(token.into()), Token::Uint(token_id)],
+    );
+    
+    Ok(())
+}
+
+// (kind, ticket contract, token ID)
+fn get_gate(env: &impl Env, dj_address: [u8; 20]) -> (u32, [u8; 20], ethabi::Uint) {
+    (
+        get_u32(env, &get_gate_key(&dj_address, 0)),
+        get_address(env, &get_gate_key(&dj_address, 1)).unwrap_or_default(),
+        get_uint(env, &get_gate_key(&dj_address, 2)),
+    )
+}
+
+// Whether `account` passes the DJ's ticket gate; always true without one
+fn holds_ticket(env: &impl Env, dj_address: [u8; 20], account: [u8; 20]) -> Result<bool, Error> {
+    let (kind, token, token_id) = get_gate(env, dj_address);
+    let balance = match kind {
+        GATE_NONE => return Ok(true),
+        GATE_ERC721 => get_token_balance(env, token, account, None)?,
+        _ => get_token_balance(env, token, account, Some(token_id))?,
+    };
+    Ok(!balance.is_zero())
+}
+
+// Quadratic voting: instead of one vote per song, each voter gets a number of credits
+// per set and casting k votes on a song costs k² of them. The mode can only change
+// between sets, and it replaces the vote budget and downvotes
+fn set_quadratic_voting(env: &mut impl Env, enabled: bool, credits: u32) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_dj(env, origin), Error::NotDj);
+    ensure!(!is_set_active(env, origin), Error::SetAlreadyActive);
+    
+    save_bool(env, &get_quadratic_voting_key(&origin, 0), enabled);
+    save_u32(env, &get_quadratic_voting_key(&origin, 1), credits);
+    
+    emit_event(
+        env,
+        &[EVENT_QUADRATIC_VOTING_SET, address_topic(&origin)],
+        &[Token::Bool(enabled), Token::Uint(credits.into())],
+    );
+    
+    Ok(())
+}
+
+fn is_quadratic_voting(env: &impl Env, dj_address: [u8; 20]) -> bool {
+    get_bool(env, &get_quadratic_voting_key(&dj_address, 0))
+}
+
+// (enabled, credits per voter per set)
+fn get_quadratic_voting(env: &impl Env, dj_address: [u8; 20]) -> (bool, u32) {
+    (is_quadratic_voting(env, dj_address), get_u32(env, &get_quadratic_voting_key(&dj_address, 1)))
+}
+
+fn get_remaining_credits(env: &impl Env, voter: [u8; 20], dj_address: [u8; 20], set_id: u32) -> u32 {
+    let (_, credits) = get_quadratic_voting(env, dj_address);
+    credits.saturating_sub(get_u32(env, &get_credits_spent_key(env, &voter, &dj_address, set_id)))
+}
+
+// Votes the voter has on the song: their quadratic count or the weight their vote was
+// cast with, and 1 for a vote from before either was recorded
+fn get_votes_cast(env: &impl Env, voter: [u8; 20], dj_address: [u8; 20], set_id: u32, song_id: u32) -> u32 {
+    match get_u32(env, &get_votes_cast_key(env, &voter, &dj_address, set_id, song_id)) {
+        0 => has_voted(env, voter, dj_address, set_id, song_id) as u32,
+        votes => votes,
+    }
+}
+
+fn refund_credits(env: &mut impl Env, voter: [u8; 20], dj_address: [u8; 20], set_id: u32, credits: u64) {
+    let spent_key = get_credits_spent_key(env, &voter, &dj_address, set_id);
+    let spent = get_u32(env, &spent_key);
+    save_u32(env, &spent_key, (u64::from(spent).saturating_sub(credits)) as u32);
+}
+
+// Adds `count` votes to the caller's votes on the song, paying the difference in
+// quadratic cost; returns the song's new vote total
+fn cast_votes(env: &mut impl Env, dj_address: [u8; 20], song_id: u32, count: u32) -> Result<u32, Error> {
+    let voter = env.caller();
+    
+    ensure!(is_set_active(env, dj_address), Error::SetNotActive);
+    ensure!(is_quadratic_voting(env, dj_address), Error::WrongVotingMode);
+    ensure!(holds_ticket(env, dj_address, voter)?, Error::NotTicketHolder);
+    ensure!(count > 0, Error::InvalidParams);
+    let set_id = get_current_set_id(env, dj_address);
+    
+    ensure!(song_id < get_song_count(env, dj_address, set_id), Error::SongNotFound);
+    ensure!(get_merged_into(env, dj_address, set_id, song_id).is_none(), Error::SongWasMerged);
+    ensure!(!is_song_removed(env, dj_address, set_id, song_id), Error::SongWasRemoved);
+    ensure!(get_played_at(env, dj_address, set_id, song_id) == 0, Error::SongAlreadyPlayed);
+    ensure!(!is_song_pending(env, dj_address, set_id, song_id), Error::SongPending);
+    
+    let cast_key = get_votes_cast_key(env, &voter, &dj_address, set_id, song_id);
+    let cast = get_u32(env, &cast_key);
+    let new_cast = cast.checked_add(count).ok_or(Error::InsufficientCredits)?;
+    let cost = u64::from(new_cast).pow(2) - u64::from(cast).pow(2);
+    let cost = u32::try_from(cost).map_err(|_| Error::InsufficientCredits)?;
+    ensure!(cost <= get_remaining_credits(env, voter, dj_address, set_id), Error::InsufficientCredits);
+    
+    let spent_key = get_credits_spent_key(env, &voter, &dj_address, set_id);
+    let spent = get_u32(env, &spent_key);
+    save_u32(env, &spent_key, spent.checked_add(cost).ok_or(Error::InsufficientCredits)?);
+    
+    // A voter's stake on a song ages from their first vote on it
+    if cast == 0 {
+        save_u64(env, &get_voted_at_key(env, &voter, &dj_address, set_id, song_id), env.now());
+    }
+    save_u32(env, &cast_key, new_cast);
+    save_bool(env, &get_has_voted_key(env, &voter, &dj_address, set_id, song_id), true);
+    record_voter(env, voter, dj_address, set_id, song_id);
+    
+    let votes_key = get_votes_key(&dj_address, set_id, song_id);
+    let votes = get_u32(env, &votes_key).saturating_add(count);
+    save_u32(env, &votes_key, votes);
+    
+    emit_event(
+        env,
+        &[EVENT_VOTES_CAST, address_topic(&dj_address), u32_topic(song_id), address_topic(&voter)],
+        &[Token::Uint(new_cast.into()), Token::Uint(votes.into())],
+    );
+    
+    Ok(votes)
+}
+
+// Takes back `count` of the caller's votes on the song and refunds the credits above
+// the cost of the votes they keep; returns the song's new vote total
+fn withdraw_votes(env: &mut impl Env, dj_address: [u8; 20], song_id: u32, count: u32) -> Result<u32, Error> {
+    let voter = env.caller();
+    
+    ensure!(is_set_active(env, dj_address), Error::SetNotActive);
+    ensure!(is_quadratic_voting(env, dj_address), Error::WrongVotingMode);
+    ensure!(holds_ticket(env, dj_address, voter)?, Error::NotTicketHolder);
+    ensure!(count > 0, Error::InvalidParams);
+    let set_id = get_current_set_id(env, dj_address);
+    
+    ensure!(song_id < get_song_count(env, dj_address, set_id), Error::SongNotFound);
+    
+    let cast_key = get_votes_cast_key(env, &voter, &dj_address, set_id, song_id);
+    let cast = get_u32(env, &cast_key);
+    ensure!(count <= cast, Error::NotVoted);
+    
+    let new_cast = cast - count;
+    refund_credits(env, voter, dj_address, set_id, u64::from(cast).pow(2) - u64::from(new_cast).pow(2));
+    save_u32(env, &cast_key, new_cast);
+    if new_cast == 0 {
+        save_bool(env, &get_has_voted_key(env, &voter, &dj_address, set_id, song_id), false);
+    }
+    
+    let votes_key = get_votes_key(&dj_address, set_id, song_id);
+    let votes = get_u32(env, &votes_key).saturating_sub(count);
+    save_u32(env, &votes_key, votes);
+    
+    emit_event(
+        env,
+        &[EVENT_VOTES_WITHDRAWN, address_topic(&dj_address), u32_topic(song_id), address_topic(&voter)],
+        &[Token::Uint(new_cast.into()), Token::Uint(votes.into())],
+    );
+    
+    Ok(votes)
+}
+
+fn unvote(env: &mut impl Env, dj_address: [u8; 20], song_id: u32) -> Result<(), Error> {
+    let voter = env.caller();
+    
+    // Check if the DJ's set is currently active
+    ensure!(is_set_active(env, dj_address), Error::SetNotActive);
+    ensure!(!is_quadratic_voting(env, dj_address), Error::WrongVotingMode);
+    ensure!(holds_ticket(env, dj_address, voter)?, Error::NotTicketHolder);
+    let set_id = get_current_set_id(env, dj_address);
+    
+    // Check if song exists
+    let song_key = get_song_key(&dj_address, set_id, song_id);
+    ensure!(get_string(env, &song_key, MAX_SONG_NAME_LEN).is_some(), Error::SongNotFound);
+    
+    // Check if the user has actually voted
+    let has_voted_key = get_has_voted_key(env, &voter, &dj_address, set_id, song_id);
+    ensure!(get_bool(env, &has_voted_key), Error::NotVoted);
+    
+    // Remove the vote record and refund the budget
+    let weight = get_votes_cast(env, voter, dj_address, set_id, song_id);
+    save_bool(env, &has_voted_key, false);
+    save_u32(env, &get_votes_cast_key(env, &voter, &dj_address, set_id, song_id), 0);
+    refund_vote(env, voter, dj_address, set_id);
+    
+    // Decrease the vote count by the weight the vote was cast with
+    let votes_key = get_votes_key(&dj_address, set_id, song_id);
+    let current_votes = get_u32(env, &votes_key);
+    let new_votes = current_votes.saturating_sub(weight);
+    if current_votes > 0 {
+        save_u32(env, &votes_key, new_votes);
+    }
+    
+    emit_event(
+        env,
+        &[EVENT_UNVOTED, address_topic(&dj_address), u32_topic(song_id), address_topic(&voter)],
+        &[Token::Uint(new_votes.into())],
+    );
+    
+    Ok(())
+}
+
+fn get_votes(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> u32 {
+    let votes_key = get_votes_key(&dj_address, set_id, song_id);
+    get_u32(env, &votes_key)
+}
+
+fn has_voted(env: &impl Env, voter: [u8; 20], dj_address: [u8; 20], set_id: u32, song_id: u32) -> bool {
+    let has_voted_key = get_has_voted_key(env, &voter, &dj_address, set_id, song_id);
+    get_bool(env, &has_voted_key)
+}
+
+fn clear_votes(env: &mut impl Env, dj_address: [u8; 20], song_id: u32) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    ensure!(is_authorized_for(env, &ROLE_MODERATOR, origin), Error::MissingRole);
+    
+    let set_id = get_current_set_id(env, dj_address);
+    let votes_key = get_votes_key(&dj_address, set_id, song_id);
+    save_u32(env, &votes_key, 0);
+    save_u32(env, &get_song_attribute_key(PREFIX_DOWNVOTES, &dj_address, set_id, song_id), 0);
+    // Decayed scores are rebuilt from the voters, so remember which votes were wiped
+    save_u64(env, &get_song_attribute_key(PREFIX_VOTES_CLEARED_AT, &dj_address, set_id, song_id), env.now());
+    
+    emit_event(env, &[EVENT_VOTES_CLEARED, address_topic(&dj_address), u32_topic(song_id)], &[]);
+    
+    Ok(())
+}
+
+// DJ Set Management Functions
+fn start_set(env: &mut impl Env, dj_address: [u8; 20]) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    // Only the DJ themselves, the owner or a delegate can start a set
+    ensure!(can_act_for(env, &origin, dj_address, PERMISSION_START_STOP_SET), Error::Unauthorized);
+    ensure!(is_dj(env, dj_address), Error::NotRegisteredDj);
+    ensure!(!is_set_active(env, dj_address), Error::SetAlreadyActive);
+    
+    // Open the next set with whatever the DJ queued while off air; votes and voter
+    // flags start empty
+    let previous_set_id = get_current_set_id(env, dj_address);
+    let set_id = previous_set_id + 1;
+    carry_queue_forward(env, dj_address, previous_set_id, set_id);
+    save_u32(env, &get_current_set_key(&dj_address), set_id);
+    
+    // Mark set as active
+    let start_time = env.now();
+    save_bool(env, &get_set_active_key(&dj_address), true);
+    save_u64(env, &get_set_start_time_key(&dj_address, set_id), start_time);
+    
+    // Add to active DJs list
+    let count_key = get_active_dj_count_key();
+    let count = get_u32(env, &count_key);
+    save_address(env, &get_active_dj_key(count), &dj_address);
+    save_u32(env, &count_key, count + 1);
+    
+    emit_event(
+        env,
+        &[EVENT_SET_STARTED, address_topic(&dj_address), u32_topic(set_id)],
+        &[Token::Uint(start_time.into())],
+    );
+    
+    Ok(())
+}
+
+// Moves the songs added to a set after it stopped (or, for a DJ's first set, all of
+// them) into the set being opened, dropping removed, merged and played entries, and
+// trims them off the finished set so its history stays as it was
+fn carry_queue_forward(env: &mut impl Env, dj_address: [u8; 20], from_set: u32, to_set: u32) {
+    let queue_start = get_u32(env, &get_set_queue_start_key(&dj_address, from_set));
+    let song_count = get_song_count(env, dj_address, from_set);
+    let mut next_id = 0;
+    
+    for song_id in queue_start..song_count {
+        if is_song_removed(env, dj_address, from_set, song_id)
+            || get_merged_into(env, dj_address, from_set, song_id).is_some()
+            || get_played_at(env, dj_address, from_set, song_id) != 0
+        {
+            continue;
+        }
+        let Some(song_name) = get_string(env, &get_song_key(&dj_address, from_set, song_id), MAX_SONG_NAME_LEN) else {
+            continue;
+        };
+        
+        save_string(env, &get_song_key(&dj_address, to_set, next_id), &song_name);
+        let (title, artist, source_uri, duration, added_by, added_at) = get_song_details(env, dj_address, from_set, song_id).unwrap_or_default();
+        // Songs added through the string API have no details to copy
+        if get_string(env, &get_song_attribute_key(PREFIX_SONG_TITLE, &dj_address, from_set, song_id), MAX_SONG_TITLE_LEN).is_some() {
+            save_song_details(env, dj_address, to_set, next_id, &title, &artist, &source_uri, duration);
+        }
+        save_address(env, &get_song_attribute_key(PREFIX_SONG_ADDED_BY, &dj_address, to_set, next_id), &added_by);
+        save_u64(env, &get_song_attribute_key(PREFIX_SONG_ADDED_AT, &dj_address, to_set, next_id), added_at);
+        let dedup_key = normalized_song_key(env, &song_name, &source_uri);
+        save_u32(env, &get_song_index_key(env, &dj_address, to_set, &dedup_key), next_id + 1);
+        next_id += 1;
+    }
+    
+    save_u32(env, &get_song_count_key(&dj_address, to_set), next_id);
+    save_u32(env, &get_song_count_key(&dj_address, from_set), queue_start);
+}
+
+fn stop_set(env: &mut impl Env, dj_address: [u8; 20]) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    // Only the DJ themselves, the owner or a delegate can stop a set
+    ensure!(can_act_for(env, &origin, dj_address, PERMISSION_START_STOP_SET), Error::Unauthorized);
+    ensure!(is_set_active(env, dj_address), Error::SetNotActive);
+    
+    // Mark set as inactive
+    save_bool(env, &get_set_active_key(&dj_address), false);
+    
+    // Record end time for historical purposes
+    let set_id = get_current_set_id(env, dj_address);
+    let end_time = env.now();
+    save_u64(env, &get_set_end_time_key(&dj_address, set_id), end_time);
+    let song_count = get_song_count(env, dj_address, set_id);
+    save_u32(env, &get_set_queue_start_key(&dj_address, set_id), song_count);
+    
+    // Remove from active DJs list
+    remove_from_active_djs(env, &dj_address);
+    
+    emit_event(
+        env,
+        &[EVENT_SET_STOPPED, address_topic(&dj_address), u32_topic(set_id)],
+        &[Token::Uint(end_time.into())],
+    );
+    
+    Ok(())
+}
+
+fn is_set_active(env: &impl Env, dj_address: [u8; 20]) -> bool {
+    get_bool(env, &get_set_active_key(&dj_address))
+}
+
+// ID of the DJ's most recent set; 0 until the first set is started
+fn get_current_set_id(env: &impl Env, dj_address: [u8; 20]) -> u32 {
+    get_u32(env, &get_current_set_key(&dj_address))
+}
+
+fn get_set_info(env: &impl Env, dj_address: [u8; 20], set_id: u32) -> (u64, u64, u32) {
+    let start_time = get_u64(env, &get_set_start_time_key(&dj_address, set_id));
+    let end_time = get_u64(env, &get_set_end_time_key(&dj_address, set_id));
+    let song_count = get_song_count(env, dj_address, set_id);
+    
+    (start_time, end_time, song_count)
+}
+
+fn remove_from_active_djs(env: &mut impl Env, dj_address: &[u8; 20]) {
+    let count_key = get_active_dj_count_key();
+    let count = get_u32(env, &count_key);
+    
+    // Find and remove the DJ
+    let mut found_index = None;
+    for i in 0..count {
+        if let Some(addr) = get_address(env, &get_active_dj_key(i)) {
+            if addr == *dj_address {
+                found_index = Some(i);
+                break;
+            }
+        }
+    }
+    
+    if let Some(index) = found_index {
+        // Move last DJ to this position
+        if index < count - 1 {
+            if let Some(last_dj) = get_address(env, &get_active_dj_key(count - 1)) {
+                save_address(env, &get_active_dj_key(index), &last_dj);
+            }
+        }
+        save_u32(env, &count_key, count - 1);
+    }
+}
+
+fn get_active_djs(env: &impl Env) -> Vec<[u8; 20]> {
+    get_active_djs_page(env, 0, u32::MAX).0
+}
+
+fn get_all_djs(env: &impl Env) -> Vec<[u8; 20]> {
+    get_all_djs_page(env, 0, u32::MAX).0
+}
+
+// Up to `limit` active DJs starting at `offset`, plus the total number of active DJs
+fn get_active_djs_page(env: &impl Env, offset: u32, limit: u32) -> (Vec<[u8; 20]>, u32) {
+    let count = get_u32(env, &get_active_dj_count_key());
+    (get_dj_range(env, get_active_dj_key, count, offset, limit), count)
+}
+
+// Up to `limit` registered DJs starting at `offset`, plus the total number ever registered
+fn get_all_djs_page(env: &impl Env, offset: u32, limit: u32) -> (Vec<[u8; 20]>, u32) {
+    let count = get_u32(env, &get_all_dj_count_key());
+    (get_dj_range(env, get_all_dj_key, count, offset, limit), count)
+}
+
+fn get_dj_range(env: &impl Env, list_key: fn(u32) -> [u8; 32], count: u32, offset: u32, limit: u32) -> Vec<[u8; 20]> {
+    let end = offset.saturating_add(limit).min(count);
+    let mut djs = Vec::new();
+    
+    for i in offset..end {
+        if let Some(dj) = get_address(env, &list_key(i)) {
+            djs.push(dj);
+        }
+    }
+    
+    djs
+}
+
+// Get all songs with votes (works for both active and past sets)
+fn get_all_songs_with_votes(env: &impl Env, dj_address: [u8; 20], set_id: u32) -> Vec<(u32, Vec<u8>, u32)> {
+    let song_count = get_song_count(env, dj_address, set_id);
+    let mut songs = Vec::new();
+    
+    for i in 0..song_count {
+        // Skip removed and played songs
+        if !is_queued(env, dj_address, set_id, i) {
+            continue;
+        }
+        
+        let song = get_song(env, dj_address, set_id, i);
+        let votes = get_votes(env, dj_address, set_id, i);
+        songs.push((i, song, votes));
+    }
+    
+    songs
+}
+
+// Queued songs among song IDs [offset, offset + limit), plus the set's total song count.
+// Pages follow song IDs, so a page can come back short when songs were removed or played
+fn get_songs_with_votes_page(env: &impl Env, dj_address: [u8; 20], set_id: u32, offset: u32, limit: u32) -> (Vec<(u32, Vec<u8>, u32)>, u32) {
+    let song_count = get_song_count(env, dj_address, set_id);
+    let end = offset.saturating_add(limit).min(song_count);
+    let mut songs = Vec::new();
+    
+    for i in offset..end {
+        if !is_queued(env, dj_address, set_id, i) {
+            continue;
+        }
+        songs.push((i, get_song(env, dj_address, set_id, i), get_votes(env, dj_address, set_id, i)));
+    }
+    
+    (songs, song_count)
+}
+
+// Cursor iteration: up to `limit` queued songs with ID >= `cursor`, plus the cursor for
+// the next call. Removed and played songs are skipped on their flags alone, without
+// loading their names, and iteration is done once the cursor reaches the song count
+fn get_songs_with_votes_from(env: &impl Env, dj_address: [u8; 20], set_id: u32, cursor: u32, limit: u32) -> (Vec<(u32, Vec<u8>, u32)>, u32) {
+    let song_count = get_song_count(env, dj_address, set_id);
+    let mut songs = Vec::new();
+    let mut i = cursor.min(song_count);
+    
+    while i < song_count && (songs.len() as u32) < limit {
+        if is_queued(env, dj_address, set_id, i) {
+            songs.push((i, get_song(env, dj_address, set_id, i), get_votes(env, dj_address, set_id, i)));
+        }
+        i += 1;
+    }
+    
+    (songs, i)
+}
+
+// A song is in the queue once approved, until it is removed or played, and while not auto-hidden
+fn is_queued(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> bool {
+    !is_song_removed(env, dj_address, set_id, song_id)
+        && get_played_at(env, dj_address, set_id, song_id) == 0
+        && !is_song_pending(env, dj_address, set_id, song_id)
+        && !is_song_hidden(env, dj_address, set_id, song_id)
+}
+
+// Queue ranking over (song_id, ranking_score): highest score first, ties go to the song queued earliest
+fn rank_order(a: &(u32, i64), b: &(u32, i64)) -> Ordering {
+    b.1.cmp(&a.1).then(a.0.cmp(&b.0))
+}
+
+// The n highest-ranked queued songs as (song_id, ranking_score)
+fn get_ranked_songs(env: &impl Env, dj_address: [u8; 20], set_id: u32, n: u32) -> Vec<(u32, i64)> {
+    let song_count = get_song_count(env, dj_address, set_id);
+    let mut ranked = Vec::new();
+    
+    for i in 0..song_count {
+        if !is_queued(env, dj_address, set_id, i) {
+            continue;
+        }
+        ranked.push((i, get_ranking_score(env, dj_address, set_id, i)));
+    }
+    
+    ranked.sort_by(rank_order);
+    ranked.truncate(n as usize);
+    ranked
+}
+
+fn get_top_songs(env: &impl Env, dj_address: [u8; 20], set_id: u32, n: u32) -> Vec<(u32, Vec<u8>, u32)> {
+    // Only load names and vote counts for the songs actually returned
+    get_ranked_songs(env, dj_address, set_id, n).into_iter()
+        .map(|(id, _)| (id, get_song(env, dj_address, set_id, id), get_votes(env, dj_address, set_id, id)))
+        .collect()
+}
+
+// Top songs as (song_id, name, upvotes, downvotes, ranking_score)
+fn get_top_songs_with_scores(env: &impl Env, dj_address: [u8; 20], set_id: u32, n: u32) -> Vec<(u32, Vec<u8>, u32, u32, i64)> {
+    get_ranked_songs(env, dj_address, set_id, n).into_iter()
+        .map(|(id, score)| (
+            id,
+            get_song(env, dj_address, set_id, id),
+            get_votes(env, dj_address, set_id, id),
+            get_downvotes(env, dj_address, set_id, id),
+            score,
+        ))
+        .collect()
+}
+
+// 1-based position of a song in the ranked queue
+fn get_queue_position(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> Result<u32, Error> {
+    let song_count = get_song_count(env, dj_address, set_id);
+    ensure!(song_id < song_count, Error::SongNotFound);
+    ensure!(!is_song_removed(env, dj_address, set_id, song_id), Error::SongWasRemoved);
+    ensure!(get_played_at(env, dj_address, set_id, song_id) == 0, Error::SongAlreadyPlayed);
+    ensure!(!is_song_pending(env, dj_address, set_id, song_id), Error::SongPending);
+    ensure!(!is_song_hidden(env, dj_address, set_id, song_id), Error::SongHidden);
+    
+    let song = (song_id, get_ranking_score(env, dj_address, set_id, song_id));
+    let mut position = 1;
+    for i in 0..song_count {
+        if i == song_id || !is_queued(env, dj_address, set_id, i) {
+            continue;
+        }
+        if rank_order(&(i, get_ranking_score(env, dj_address, set_id, i)), &song) == Ordering::Less {
+            position += 1;
+        }
+    }
+    
+    Ok(position)
+}
+
+// Playback: the most recently played song of a live set is "now playing",
+// and the order songs were played in is kept as the set's history
+fn advance(env: &mut impl Env, dj_address: [u8; 20]) -> Result<u32, Error> {
+    let origin = env.caller();
+    
+    ensure!(can_act_for(env, &origin, dj_address, PERMISSION_MARK_PLAYED), Error::Unauthorized);
+    
+    let set_id = get_current_set_id(env, dj_address);
+    let next = get_top_songs(env, dj_address, set_id, 1);
+    let song_id = match next.first() {
+        Some((song_id, _, _)) => *song_id,
+        None => return Err(Error::QueueEmpty),
+    };
+    
+    mark_played(env, dj_address, song_id)?;
+    
+    Ok(song_id)
+}
+
+fn mark_played(env: &mut impl Env, dj_address: [u8; 20], song_id: u32) -> Result<(), Error> {
+    let origin = env.caller();
+    
+    // Only the DJ themselves, the owner or a delegate can drive playback
+    ensure!(can_act_for(env, &origin, dj_address, PERMISSION_MARK_PLAYED), Error::Unauthorized);
+    ensure!(is_set_active(env, dj_address), Error::SetNotActive);
+    
+    let set_id = get_current_set_id(env, dj_address);
+    ensure!(song_id < get_song_count(env, dj_address, set_id), Error::SongNotFound);
+    ensure!(!is_song_removed(env, dj_address, set_id, song_id), Error::SongWasRemoved);
+    ensure!(get_played_at(env, dj_address, set_id, song_id) == 0, Error::SongAlreadyPlayed);
+    ensure!(!is_song_pending(env, dj_address, set_id, song_id), Error::SongPending);
+    
+    let played_at = env.now();
+    save_u64(env, &get_song_played_at_key(&dj_address, set_id, song_id), played_at);
+    
+    let count_key = get_play_count_key(&dj_address, set_id);
+    let count = get_u32(env, &count_key);
+    save_u32(env, &get_play_history_key(&dj_address, set_id, count), song_id);
+    save_u32(env, &count_key, count + 1);
+    
+    emit_event(
+        env,
+        &[EVENT_SONG_PLAYED, address_topic(&dj_address), u32_topic(set_id), u32_topic(song_id)],
+        &[Token::Uint(played_at.into())],
+    );
+    
+    Ok(())
+}
+
+// Timestamp the song was played at, 0 if it hasn't been played
+fn get_played_at(env: &impl Env, dj_address: [u8; 20], set_id: u32, song_id: u32) -> u64 {
+    get_u64(env, &get_song_played_at_key(&dj_address, set_id, song_id))
 }
 
-fn get_votes(dj_address: [u8; 20], song_id: u32) -> u32 {
-    let votes_key = get_votes_key(&dj_address, song_id);
-    get_u32(&votes_key)
+fn get_play_count(env: &impl Env, dj_address: [u8; 20], set_id: u32) -> u32 {
+    get_u32(env, &get_play_count_key(&dj_address, set_id))
 }
 
-fn has_voted(voter: [u8; 20], dj_address: [u8; 20], song_id: u32) -> bool {
-    let has_voted_key = get_has_voted_key(&voter, &dj_address, song_id);
-    get_bool(&has_voted_key)
+// Last played song of the live set as (song_id, name, played_at)
+fn now_playing(env: &impl Env, dj_address: [u8; 20]) -> Option<(u32, Vec<u8>, u64)> {
+    if !is_set_active(env, dj_address) {
+        return None;
+    }
+    
+    let set_id = get_current_set_id(env, dj_address);
+    let count = get_play_count(env, dj_address, set_id);
+    if count == 0 {
+        return None;
+    }
+    
+    let song_id = get_u32(env, &get_play_history_key(&dj_address, set_id, count - 1));
+    let name = get_song(env, dj_address, set_id, song_id);
+    let played_at = get_played_at(env, dj_address, set_id, song_id);
+    Some((song_id, name, played_at))
 }
 
-fn clear_votes(dj_address: [u8; 20], song_id: u32) {
-    let origin = get_origin();
+// Played songs in play order as (song_id, name, votes, played_at)
+fn get_play_history(env: &impl Env, dj_address: [u8; 20], set_id: u32, offset: u32, limit: u32) -> Vec<(u32, Vec<u8>, u32, u64)> {
+    let count = get_play_count(env, dj_address, set_id);
+    let end = offset.saturating_add(limit).min(count);
+    let mut history = Vec::new();
     
-    assert!(is_owner(&origin), "NOT_OWNER");
+    for i in offset..end {
+        let song_id = get_u32(env, &get_play_history_key(&dj_address, set_id, i));
+        let name = get_song(env, dj_address, set_id, song_id);
+        let votes = get_votes(env, dj_address, set_id, song_id);
+        let played_at = get_played_at(env, dj_address, set_id, song_id);
+        history.push((song_id, name, votes, played_at));
+    }
     
-    let votes_key = get_votes_key(&dj_address, song_id);
-    save_u32(&votes_key, 0);
+    history
 }
 
-// DJ Set Management Functions
-fn start_set(dj_address: [u8; 20]) {
-    let origin = get_origin();
+// Delegates: addresses a DJ authorizes to run parts of their own room
+fn add_delegate(env: &mut impl Env, delegate: [u8; 20], permissions: u32) -> Result<(), Error> {
+    let origin = env.caller();
     
-    // Only the DJ themselves or the owner can start a set
-    assert!(origin == dj_address || is_owner(&origin), "UNAUTHORIZED");
-    assert!(is_dj(dj_address), "NOT_REGISTERED_DJ");
-    assert!(!is_set_active(dj_address), "SET_ALREADY_ACTIVE");
+    ensure!(is_dj(env, origin), Error::NotDj);
+    ensure!(delegate != origin && delegate != [0u8; 20], Error::InvalidParams);
+    ensure!(permissions != 0 && permissions & !ALL_PERMISSIONS == 0, Error::InvalidParams);
     
-    // Mark set as active
-    save_bool(&get_set_active_key(&dj_address), true);
-    save_u64(&get_set_start_time_key(&dj_address), get_timestamp());
+    // New delegates are appended to the DJ's list; existing ones just get new permissions
+    let permissions_key = get_delegate_permissions_key(env, &origin, &delegate);
+    if get_u32(env, &permissions_key) == 0 {
+        let count_key = get_delegate_count_key(&origin);
+        let count = get_u32(env, &count_key);
+        save_address(env, &get_delegate_key(&origin, count), &delegate);
+        save_u32(env, &count_key, count + 1);
+    }
+    save_u32(env, &permissions_key, permissions);
     
-    // Add to active DJs list
-    let count_key = get_active_dj_count_key();
-    let count = get_u32(&count_key);
-    save_address(&get_active_dj_key(count), &dj_address);
-    save_u32(&count_key, count + 1);
+    emit_event(
+        env,
+        &[EVENT_DELEGATE_UPDATED, address_topic(&origin), address_topic(&delegate)],
+        &[Token::Uint(permissions.into())],
+    );
+    
+    Ok(())
 }
 
-fn stop_set(dj_address: [u8; 20]) {
-    let origin = get_origin();
+fn remove_delegate(env: &mut impl Env, delegate: [u8; 20]) -> Result<(), Error> {
+    let origin = env.caller();
     
-    // Only the DJ themselves or the owner can stop a set
-    assert!(origin == dj_address || is_owner(&origin), "UNAUTHORIZED");
-    assert!(is_set_active(dj_address), "SET_NOT_ACTIVE");
+    let permissions_key = get_delegate_permissions_key(env, &origin, &delegate);
+    ensure!(get_u32(env, &permissions_key) != 0, Error::NotDelegate);
+    save_u32(env, &permissions_key, 0);
     
-    // Mark set as inactive
-    save_bool(&get_set_active_key(&dj_address), false);
+    // Move the last delegate into the freed slot
+    let count_key = get_delegate_count_key(&origin);
+    let count = get_u32(env, &count_key);
+    for i in 0..count {
+        if get_address(env, &get_delegate_key(&origin, i)) == Some(delegate) {
+            if i < count - 1 {
+                if let Some(last) = get_address(env, &get_delegate_key(&origin, count - 1)) {
+                    save_address(env, &get_delegate_key(&origin, i), &last);
+                }
+            }
+            save_u32(env, &count_key, count - 1);
+            break;
+        }
+    }
     
-    // Record end time for historical purposes
-    save_u64(&get_set_end_time_key(&dj_address), get_timestamp());
+    emit_event(env, &[EVENT_DELEGATE_REMOVED, address_topic(&origin), address_topic(&delegate)], &[]);
     
-    // Remove from active DJs list
-    remove_from_active_djs(&dj_address);
+    Ok(())
 }
 
-fn is_set_active(dj_address: [u8; 20]) -> bool {
-    get_bool(&get_set_active_key(&dj_address))
+fn get_delegate_permissions(env: &impl Env, dj_address: [u8; 20], delegate: [u8; 20]) -> u32 {
+    get_u32(env, &get_delegate_permissions_key(env, &dj_address, &delegate))
 }
 
-fn remove_from_active_djs(dj_address: &[u8; 20]) {
-    let count_key = get_active_dj_count_key();
-    let count = get_u32(&count_key);
+fn get_delegates(env: &impl Env, dj_address: [u8; 20]) -> Vec<([u8; 20], u32)> {
+    let count = get_u32(env, &get_delegate_count_key(&dj_address));
+    let mut delegates = Vec::new();
     
-    // Find and remove the DJ
-    let mut found_index = None;
     for i in 0..count {
-        if let Some(addr) = get_address(&get_active_dj_key(i)) {
-            if addr == *dj_address {
-                found_index = Some(i);
-                break;
-            }
+        if let Some(delegate) = get_address(env, &get_delegate_key(&dj_address, i)) {
+            delegates.push((delegate, get_delegate_permissions(env, dj_address, delegate)));
         }
     }
     
-    if let Some(index) = found_index {
-        // Move last DJ to this position
-        if index < count - 1 {
-            if let Some(last_dj) = get_address(&get_active_dj_key(count - 1)) {
-                save_address(&get_active_dj_key(index), &last_dj);
-            }
-        }
-        save_u32(&count_key, count - 1);
-    }
+    delegates
 }
 
-fn get_active_djs() -> Vec<[u8; 20]> {
-    let count_key = get_active_dj_count_key();
-    let count = get_u32(&count_key);
-    let mut djs = Vec::new();
+// DJ Metadata functions
+fn set_dj_metadata(env: &mut impl Env, dj_address: [u8; 20], metadata: Vec<u8>) -> Result<(), Error> {
+    let origin = env.caller();
     
-    for i in 0..count {
-        if let Some(dj) = get_address(&get_active_dj_key(i)) {
-            djs.push(dj);
-        }
+    // Only the DJ themselves or the owner can set metadata
+    ensure!(origin == dj_address || is_owner(env, &origin), Error::Unauthorized);
+    ensure!(is_dj(env, dj_address), Error::NotRegisteredDj);
+    ensure!(metadata.len() <= MAX_METADATA_LEN, Error::MetadataTooLong);
+    
+    save_string(env, &get_dj_metadata_key(&dj_address), &metadata);
+    
+    emit_event(
+        env,
+        &[EVENT_DJ_METADATA_UPDATED, address_topic(&dj_address)],
+        &[Token::String(String::from_utf8_lossy(&metadata).into_owned())],
+    );
+    
+    Ok(())
+}
+
+fn get_dj_metadata(env: &impl Env, dj_address: [u8; 20]) -> Vec<u8> {
+    get_string(env, &get_dj_metadata_key(&dj_address), MAX_METADATA_LEN).unwrap_or_default()
+}
+
+// Re-encodes up to `max_items` song names of one set and returns the song ID to resume from
+fn reencode_song_names(env: &mut impl Env, dj_address: [u8; 20], set_id: u32, from_song_id: u32, max_items: u32) -> u32 {
+    let song_count = get_song_count(env, dj_address, set_id);
+    let end = from_song_id.saturating_add(max_items).min(song_count);
+    for i in from_song_id..end {
+        reencode_string(env, &get_song_key(&dj_address, set_id, i), MAX_SONG_NAME_LEN);
     }
     
-    djs
+    end.max(from_song_id)
 }
 
-fn get_all_djs() -> Vec<[u8; 20]> {
-    let count_key = get_all_dj_count_key();
-    let count = get_u32(&count_key);
-    let mut djs = Vec::new();
+// Schema versioning
+//
+// Deployments from before the version was stored read as version 1. `migrate` runs the
+// step from the stored version to the next one in batches and bumps the version once the
+// step completes. Reads understand both layouts, so the contract stays usable mid-migration
+fn get_schema_version(env: &impl Env) -> u32 {
+    match get_u32(env, &get_schema_version_key()) {
+        0 => 1,
+        version => version,
+    }
+}
+
+fn migrate(env: &mut impl Env, max_items: u32) -> Result<(u32, bool), Error> {
+    let origin = env.caller();
     
-    for i in 0..count {
-        if let Some(dj) = get_address(&get_all_dj_key(i)) {
-            djs.push(dj);
-        }
+    ensure!(is_owner(env, &origin), Error::NotOwner);
+    ensure!(max_items > 0, Error::InvalidParams);
+    
+    let version = get_schema_version(env);
+    if version >= CURRENT_SCHEMA_VERSION {
+        return Ok((version, true));
     }
     
-    djs
+    let step_done = match version {
+        1 => migrate_v1_strings(env, max_items),
+        _ => true,
+    };
+    if !step_done {
+        return Ok((version, false));
+    }
+    
+    for field in 0..3 {
+        save_u32(env, &get_migration_cursor_key(field), 0);
+    }
+    save_bool(env, &get_migration_cursor_key(3), false);
+    save_u32(env, &get_schema_version_key(), version + 1);
+    
+    emit_event(env, &[EVENT_SCHEMA_MIGRATED], &[Token::Uint(version.into()), Token::Uint((version + 1).into())]);
+    
+    Ok((version + 1, version + 1 >= CURRENT_SCHEMA_VERSION))
 }
 
-// Get all songs with votes (works for both active and inactive sets)
-fn get_all_songs_with_votes(dj_address: [u8; 20]) -> Vec<(u32, Vec<u8>, u32)> {
-    let song_count = get_song_count(dj_address);
-    let mut songs = Vec::new();
+// v1 -> v2: walk every DJ's metadata and the song names of all their sets. Each call
+// spends at most `max_items`, where re-encoding a value and moving on to the next set or
+// DJ each cost one, so runs of empty sets stay bounded too. Returns true once every DJ is done
+fn migrate_v1_strings(env: &mut impl Env, max_items: u32) -> bool {
+    let dj_count = get_u32(env, &get_all_dj_count_key());
+    let mut dj_index = get_u32(env, &get_migration_cursor_key(0));
+    let mut set_id = get_u32(env, &get_migration_cursor_key(1));
+    let mut song_id = get_u32(env, &get_migration_cursor_key(2));
+    let mut metadata_done = get_bool(env, &get_migration_cursor_key(3));
+    let mut budget = max_items;
     
-    for i in 0..song_count {
-        // Skip removed songs
-        if is_song_removed(dj_address, i) {
+    while budget > 0 && dj_index < dj_count {
+        let dj_address = get_address(env, &get_all_dj_key(dj_index)).unwrap_or([0u8; 20]);
+        
+        // Metadata goes first when a DJ is reached
+        if !metadata_done {
+            reencode_string(env, &get_dj_metadata_key(&dj_address), MAX_METADATA_LEN);
+            metadata_done = true;
+            budget -= 1;
             continue;
         }
         
-        let song = get_song(dj_address, i);
-        let votes = get_votes(dj_address, i);
-        songs.push((i, song, votes));
+        let next_song_id = reencode_song_names(env, dj_address, set_id, song_id, budget);
+        budget -= next_song_id - song_id;
+        song_id = next_song_id;
+        
+        if budget > 0 && song_id >= get_song_count(env, dj_address, set_id) {
+            budget -= 1;
+            song_id = 0;
+            set_id += 1;
+            if set_id > get_current_set_id(env, dj_address) {
+                set_id = 0;
+                dj_index += 1;
+                metadata_done = false;
+            }
+        }
     }
     
-    songs
+    save_u32(env, &get_migration_cursor_key(0), dj_index);
+    save_u32(env, &get_migration_cursor_key(1), set_id);
+    save_u32(env, &get_migration_cursor_key(2), song_id);
+    save_bool(env, &get_migration_cursor_key(3), metadata_done);
+    
+    dj_index >= dj_count
 }
 
-// DJ Metadata functions
-fn set_dj_metadata(dj_address: [u8; 20], metadata: Vec<u8>) {
-    let origin = get_origin();
+// Code upgrades: swap the contract's code in place, keeping storage. The code hash being
+// replaced is recorded so the owner can roll back by upgrading to it again
+fn upgrade_to(env: &mut impl Env, code_hash: [u8; 32], force: bool) -> Result<(), Error> {
+    let origin = env.caller();
     
-    // Only the DJ themselves or the owner can set metadata
-    assert!(origin == dj_address || is_owner(&origin), "UNAUTHORIZED");
-    assert!(is_dj(dj_address), "NOT_REGISTERED_DJ");
-    assert!(metadata.len() <= 512, "METADATA_TOO_LONG");
+    ensure!(is_owner(env, &origin), Error::NotOwner);
+    
+    let previous_code_hash = env.own_code_hash();
+    ensure!(code_hash != [0u8; 32] && code_hash != previous_code_hash, Error::InvalidParams);
     
-    save_string(&get_dj_metadata_key(&dj_address), &metadata);
+    // Swapping code under a live set could change behaviour mid-show
+    ensure!(force || get_u32(env, &get_active_dj_count_key()) == 0, Error::SetsActive);
+    
+    env.set_storage(&get_previous_code_hash_key(), &previous_code_hash);
+    env.set_code_hash(&code_hash);
+    
+    emit_event(env, &[EVENT_UPGRADED, previous_code_hash, code_hash], &[]);
+    
+    Ok(())
 }
 
-fn get_dj_metadata(dj_address: [u8; 20]) -> Vec<u8> {
-    get_string(&get_dj_metadata_key(&dj_address)).unwrap_or_else(|| vec![])
+// Code hash the contract ran before its last upgrade; zero if it was never upgraded
+fn get_previous_code_hash(env: &impl Env) -> [u8; 32] {
+    let mut code_hash = [0u8; 32];
+    env.get_storage(&get_previous_code_hash_key(), &mut code_hash);
+    code_hash
 }
 
 // Combined DJ info for UI
-fn get_dj_info(dj_address: [u8; 20]) -> (bool, bool, u64, u32, Vec<u8>) {
-    let is_registered = is_dj(dj_address);
-    let is_active = is_set_active(dj_address);
-    // Always return the last start time for historical purposes
-    let start_time = get_u64(&get_set_start_time_key(&dj_address));
-    let song_count = get_song_count(dj_address);
-    let metadata = get_dj_metadata(dj_address);
+fn get_dj_info(env: &impl Env, dj_address: [u8; 20]) -> (bool, bool, u64, u32, Vec<u8>) {
+    let is_registered = is_dj(env, dj_address);
+    let is_active = is_set_active(env, dj_address);
+    // Always return the last set's start time for historical purposes
+    let set_id = get_current_set_id(env, dj_address);
+    let start_time = get_u64(env, &get_set_start_time_key(&dj_address, set_id));
+    let song_count = get_song_count(env, dj_address, set_id);
+    let metadata = get_dj_metadata(env, dj_address);
     
     (is_registered, is_active, start_time, song_count, metadata)
 }
 
 // Extended DJ info including end time for historical data
-fn get_dj_info_extended(dj_address: [u8; 20]) -> (bool, bool, u64, u64, u32, Vec<u8>) {
-    let is_registered = is_dj(dj_address);
-    let is_active = is_set_active(dj_address);
-    let start_time = get_u64(&get_set_start_time_key(&dj_address));
-    let end_time = get_u64(&get_set_end_time_key(&dj_address));
-    let song_count = get_song_count(dj_address);
-    let metadata = get_dj_metadata(dj_address);
+fn get_dj_info_extended(env: &impl Env, dj_address: [u8; 20]) -> (bool, bool, u64, u64, u32, Vec<u8>) {
+    let is_registered = is_dj(env, dj_address);
+    let is_active = is_set_active(env, dj_address);
+    let set_id = get_current_set_id(env, dj_address);
+    let (start_time, end_time, song_count) = get_set_info(env, dj_address, set_id);
+    let metadata = get_dj_metadata(env, dj_address);
     
     (is_registered, is_active, start_time, end_time, song_count, metadata)
 }
 
 // Storage helpers for u64
-fn save_u64(key: &[u8; 32], value: u64) {
-    api::set_storage(StorageFlags::empty(), key, &value.to_le_bytes());
+fn save_u64(env: &mut impl Env, key: &[u8; 32], value: u64) {
+    env.set_storage(key, &value.to_le_bytes());
 }
 
-fn get_u64(key: &[u8; 32]) -> u64 {
+fn get_u64(env: &impl Env, key: &[u8; 32]) -> u64 {
     let mut buffer = [0u8; 8];
-    match api::get_storage(StorageFlags::empty(), key, &mut &mut buffer[..]) {
-        Ok(_) => u64::from_le_bytes(buffer),
-        Err(_) => 0,
+    match env.get_storage(key, &mut buffer) {
+        Some(_) => u64::from_le_bytes(buffer),
+        None => 0,
+    }
+}
+
+// Storage helpers for uint256, kept big-endian like the ABI
+fn save_uint(env: &mut impl Env, key: &[u8; 32], value: ethabi::Uint) {
+    let mut buffer = [0u8; 32];
+    value.to_big_endian(&mut buffer);
+    env.set_storage(key, &buffer);
+}
+
+fn get_uint(env: &impl Env, key: &[u8; 32]) -> ethabi::Uint {
+    let mut buffer = [0u8; 32];
+    match env.get_storage(key, &mut buffer) {
+        Some(_) => ethabi::Uint::from_big_endian(&buffer),
+        None => ethabi::Uint::zero(),
+    }
+}
+
+// Calldata decoding helpers
+fn decode_params(types: &[ParamType], data: &[u8]) -> Result<Vec<Token>, Error> {
+    decode(types, data).map_err(|_| Error::InvalidParams)
+}
+
+fn token_to_address(token: &Token) -> Result<[u8; 20], Error> {
+    match token {
+        Token::Address(addr) => Ok(addr.0),
+        _ => Err(Error::InvalidParams),
+    }
+}
+
+fn token_to_u32(token: &Token) -> Result<u32, Error> {
+    match token {
+        Token::Uint(value) if *value <= u32::MAX.into() => Ok(value.as_u32()),
+        _ => Err(Error::InvalidParams),
+    }
+}
+
+// Counts and page sizes clamp to u32 rather than rejecting large values like type(uint256).max
+fn token_to_limit(token: &Token) -> Result<u32, Error> {
+    match token {
+        Token::Uint(value) if *value > u32::MAX.into() => Ok(u32::MAX),
+        Token::Uint(value) => Ok(value.as_u32()),
+        _ => Err(Error::InvalidParams),
+    }
+}
+
+fn token_to_u64(token: &Token) -> Result<u64, Error> {
+    match token {
+        Token::Uint(value) if *value > u64::MAX.into() => Err(Error::InvalidParams),
+        Token::Uint(value) => Ok(value.as_u64()),
+        _ => Err(Error::InvalidParams),
+    }
+}
+
+// int256 values that fit in an i64
+fn token_to_i64(token: &Token) -> Result<i64, Error> {
+    match token {
+        Token::Int(value) => {
+            let low = value.low_u64() as i64;
+            ensure!(i64_to_token(low) == Token::Int(*value), Error::InvalidParams);
+            Ok(low)
+        },
+        _ => Err(Error::InvalidParams),
+    }
+}
+
+// Two's complement int256 encoding of an i64
+fn i64_to_token(value: i64) -> Token {
+    if value < 0 {
+        Token::Int(!ethabi::Uint::from(!(value as u64)))
+    } else {
+        Token::Int((value as u64).into())
+    }
+}
+
+fn token_to_bytes32(token: &Token) -> Result<[u8; 32], Error> {
+    match token {
+        Token::FixedBytes(bytes) if bytes.len() == 32 => {
+            let mut value = [0u8; 32];
+            value.copy_from_slice(bytes);
+            Ok(value)
+        },
+        _ => Err(Error::InvalidParams),
+    }
+}
+
+fn token_to_bytes(token: &Token) -> Result<Vec<u8>, Error> {
+    match token {
+        Token::String(value) => Ok(value.as_bytes().to_vec()),
+        _ => Err(Error::InvalidParams),
     }
 }
 
-fn get_timestamp() -> u64 {
-    let mut timestamp_bytes = [0u8; 32];
-    api::now(&mut timestamp_bytes);
-    let mut timestamp_u64_bytes = [0u8; 8];
-    timestamp_u64_bytes.copy_from_slice(&timestamp_bytes[0..8]);
-    u64::from_le_bytes(timestamp_u64_bytes)
+// (id, name, votes)[] as returned by getAllSongsWithVotes
+fn encode_songs_with_votes(songs: &[(u32, Vec<u8>, u32)]) -> Token {
+    Token::Array(songs.iter().map(|(id, name, votes)| {
+        Token::Tuple(vec![
+            Token::Uint((*id).into()),
+            Token::String(String::from_utf8_lossy(name).into_owned()),
+            Token::Uint((*votes).into())
+        ])
+    }).collect())
 }
 
-fn dispatch(selector: [u8; 4], data: &[u8]) {
+fn dispatch(env: &mut impl Env, selector: [u8; 4], data: &[u8]) -> Result<Vec<u8>, Error> {
     match selector {
+        SELECTOR_OWNER => {
+            Ok(encode(&[Token::Address(get_owner(env).into())]))
+        },
+        SELECTOR_PENDING_OWNER => {
+            Ok(encode(&[Token::Address(get_pending_owner(env).into())]))
+        },
+        SELECTOR_TRANSFER_OWNERSHIP => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let new_owner = token_to_address(&decoded[0])?;
+            transfer_ownership(env, new_owner)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_ACCEPT_OWNERSHIP => {
+            accept_ownership(env)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_RENOUNCE_OWNERSHIP => {
+            renounce_ownership(env)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GRANT_ROLE => {
+            let decoded = decode_params(&[ParamType::FixedBytes(32), ParamType::Address], data)?;
+            let role = token_to_bytes32(&decoded[0])?;
+            let account = token_to_address(&decoded[1])?;
+            grant_role(env, role, account)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_REVOKE_ROLE => {
+            let decoded = decode_params(&[ParamType::FixedBytes(32), ParamType::Address], data)?;
+            let role = token_to_bytes32(&decoded[0])?;
+            let account = token_to_address(&decoded[1])?;
+            revoke_role(env, role, account)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_HAS_ROLE => {
+            let decoded = decode_params(&[ParamType::FixedBytes(32), ParamType::Address], data)?;
+            let role = token_to_bytes32(&decoded[0])?;
+            let account = token_to_address(&decoded[1])?;
+            Ok(encode(&[Token::Bool(has_role(env, &role, account)?)]))
+        },
+        SELECTOR_GET_ROLE_MEMBERS => {
+            let decoded = decode_params(&[ParamType::FixedBytes(32)], data)?;
+            let role = token_to_bytes32(&decoded[0])?;
+            let members = get_role_members(env, &role)?;
+            let member_tokens: Vec<Token> = members.iter()
+                .map(|addr| Token::Address((*addr).into()))
+                .collect();
+            Ok(encode(&[Token::Array(member_tokens)]))
+        },
         SELECTOR_REGISTER_DJ => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            register_dj(dj_address);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            register_dj(env, dj_address)?;
+            Ok(encode(&[Token::Bool(true)]))
         },
         SELECTOR_REMOVE_DJ => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            remove_dj(dj_address);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            remove_dj(env, dj_address)?;
+            Ok(encode(&[Token::Bool(true)]))
         },
         SELECTOR_IS_DJ => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let is_dj_result = is_dj(dj_address);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(is_dj_result)]));
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let is_dj_result = is_dj(env, dj_address);
+            Ok(encode(&[Token::Bool(is_dj_result)]))
         },
         SELECTOR_ADD_SONG => {
-            let decoded = decode(&[ParamType::String], data)
-                .expect("Failed to decode params");
-            let song_name = if let Token::String(s) = &decoded[0] {
-                s.as_bytes().to_vec()
-            } else {
-                panic!("Invalid song name");
-            };
-            let song_id = add_song(song_name);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Uint(song_id.into())]));
+            let decoded = decode_params(&[ParamType::String], data)?;
+            let song_name = token_to_bytes(&decoded[0])?;
+            let song_id = add_song(env, song_name)?;
+            Ok(encode(&[Token::Uint(song_id.into())]))
         },
         SELECTOR_GET_SONG => {
-            let decoded = decode(&[ParamType::Address, ParamType::Uint(256)], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let song_id = if let Token::Uint(id) = &decoded[1] {
-                id.as_u32()
-            } else {
-                panic!("Invalid song ID");
-            };
-            let song = get_song(dj_address, song_id);
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let song = get_song(env, dj_address, set_id, song_id);
             let song_string = String::from_utf8_lossy(&song).into_owned();
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::String(song_string)]));
+            Ok(encode(&[Token::String(song_string)]))
         },
         SELECTOR_GET_SONG_COUNT => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let count = get_song_count(dj_address);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Uint(count.into())]));
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let count = get_song_count(env, dj_address, set_id);
+            Ok(encode(&[Token::Uint(count.into())]))
         },
         SELECTOR_VOTE => {
-            let decoded = decode(&[ParamType::Address, ParamType::Uint(256)], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let song_id = if let Token::Uint(id) = &decoded[1] {
-                id.as_u32()
-            } else {
-                panic!("Invalid song ID");
-            };
-            vote(dj_address, song_id);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            vote(env, dj_address, song_id)?;
+            Ok(encode(&[Token::Bool(true)]))
         },
         SELECTOR_GET_VOTES => {
-            let decoded = decode(&[ParamType::Address, ParamType::Uint(256)], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let song_id = if let Token::Uint(id) = &decoded[1] {
-                id.as_u32()
-            } else {
-                panic!("Invalid song ID");
-            };
-            let votes = get_votes(dj_address, song_id);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Uint(votes.into())]));
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let votes = get_votes(env, dj_address, set_id, song_id);
+            Ok(encode(&[Token::Uint(votes.into())]))
         },
         SELECTOR_HAS_VOTED => {
-            let decoded = decode(&[ParamType::Address, ParamType::Address, ParamType::Uint(256)], data)
-                .expect("Failed to decode params");
-            let mut voter = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                voter.copy_from_slice(&addr.0);
-            }
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[1] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let song_id = if let Token::Uint(id) = &decoded[2] {
-                id.as_u32()
-            } else {
-                panic!("Invalid song ID");
-            };
-            let voted = has_voted(voter, dj_address, song_id);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(voted)]));
+            let decoded = decode_params(&[ParamType::Address, ParamType::Address, ParamType::Uint(256)], data)?;
+            let voter = token_to_address(&decoded[0])?;
+            let dj_address = token_to_address(&decoded[1])?;
+            let song_id = token_to_u32(&decoded[2])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let voted = has_voted(env, voter, dj_address, set_id, song_id);
+            Ok(encode(&[Token::Bool(voted)]))
         },
         SELECTOR_CLEAR_VOTES => {
-            let decoded = decode(&[ParamType::Address, ParamType::Uint(256)], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let song_id = if let Token::Uint(id) = &decoded[1] {
-                id.as_u32()
-            } else {
-                panic!("Invalid song ID");
-            };
-            clear_votes(dj_address, song_id);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            clear_votes(env, dj_address, song_id)?;
+            Ok(encode(&[Token::Bool(true)]))
         },
         SELECTOR_START_SET => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            start_set(dj_address);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            start_set(env, dj_address)?;
+            Ok(encode(&[Token::Bool(true)]))
         },
         SELECTOR_STOP_SET => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            stop_set(dj_address);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            stop_set(env, dj_address)?;
+            Ok(encode(&[Token::Bool(true)]))
         },
         SELECTOR_IS_SET_ACTIVE => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let is_active = is_set_active(dj_address);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(is_active)]));
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let is_active = is_set_active(env, dj_address);
+            Ok(encode(&[Token::Bool(is_active)]))
         },
         SELECTOR_GET_ACTIVE_DJS => {
-            let djs = get_active_djs();
+            let djs = get_active_djs(env);
             let addresses: Vec<Token> = djs.iter()
                 .map(|addr| Token::Address((*addr).into()))
                 .collect();
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Array(addresses)]));
+            Ok(encode(&[Token::Array(addresses)]))
         },
         SELECTOR_GET_ALL_DJS => {
-            let djs = get_all_djs();
+            let djs = get_all_djs(env);
             let addresses: Vec<Token> = djs.iter()
                 .map(|addr| Token::Address((*addr).into()))
                 .collect();
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Array(addresses)]));
+            Ok(encode(&[Token::Array(addresses)]))
         },
-        SELECTOR_SET_DJ_METADATA => {
-            let decoded = decode(&[ParamType::Address, ParamType::String], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let metadata = if let Token::String(s) = &decoded[1] {
-                s.as_bytes().to_vec()
+        SELECTOR_GET_ACTIVE_DJS_PAGED | SELECTOR_GET_ALL_DJS_PAGED => {
+            let decoded = decode_params(&[ParamType::Uint(256), ParamType::Uint(256)], data)?;
+            let offset = token_to_limit(&decoded[0])?;
+            let limit = token_to_limit(&decoded[1])?.min(MAX_PAGE_SIZE);
+            let (djs, total) = if selector == SELECTOR_GET_ACTIVE_DJS_PAGED {
+                get_active_djs_page(env, offset, limit)
             } else {
-                panic!("Invalid metadata");
+                get_all_djs_page(env, offset, limit)
             };
-            set_dj_metadata(dj_address, metadata);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            let addresses: Vec<Token> = djs.iter()
+                .map(|addr| Token::Address((*addr).into()))
+                .collect();
+            Ok(encode(&[Token::Array(addresses), Token::Uint(total.into())]))
+        },
+        SELECTOR_UPGRADE_TO => {
+            let decoded = decode_params(&[ParamType::FixedBytes(32)], data)?;
+            let code_hash = token_to_bytes32(&decoded[0])?;
+            upgrade_to(env, code_hash, false)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_UPGRADE_TO_FORCED => {
+            let decoded = decode_params(&[ParamType::FixedBytes(32), ParamType::Bool], data)?;
+            let code_hash = token_to_bytes32(&decoded[0])?;
+            let force = decoded[1].clone().into_bool().ok_or(Error::InvalidParams)?;
+            upgrade_to(env, code_hash, force)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_CODE_HASH => {
+            Ok(encode(&[Token::FixedBytes(env.own_code_hash().to_vec())]))
+        },
+        SELECTOR_PREVIOUS_CODE_HASH => {
+            Ok(encode(&[Token::FixedBytes(get_previous_code_hash(env).to_vec())]))
+        },
+        SELECTOR_SCHEMA_VERSION => {
+            Ok(encode(&[Token::Uint(get_schema_version(env).into())]))
+        },
+        SELECTOR_MIGRATE => {
+            let decoded = decode_params(&[ParamType::Uint(256)], data)?;
+            let max_items = token_to_limit(&decoded[0])?;
+            let (version, done) = migrate(env, max_items)?;
+            Ok(encode(&[Token::Uint(version.into()), Token::Bool(done)]))
+        },
+        SELECTOR_SET_DJ_METADATA => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::String], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let metadata = token_to_bytes(&decoded[1])?;
+            set_dj_metadata(env, dj_address, metadata)?;
+            Ok(encode(&[Token::Bool(true)]))
         },
         SELECTOR_GET_DJ_METADATA => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let metadata = get_dj_metadata(dj_address);
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let metadata = get_dj_metadata(env, dj_address);
             let metadata_string = String::from_utf8_lossy(&metadata).into_owned();
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::String(metadata_string)]));
+            Ok(encode(&[Token::String(metadata_string)]))
         },
         SELECTOR_GET_DJ_INFO => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let (is_registered, is_active, start_time, song_count, metadata) = get_dj_info(dj_address);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Tuple(vec![
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let (is_registered, is_active, start_time, song_count, metadata) = get_dj_info(env, dj_address);
+            Ok(encode(&[Token::Tuple(vec![
                 Token::Bool(is_registered),
                 Token::Bool(is_active),
                 Token::Uint(start_time.into()),
                 Token::Uint(song_count.into()),
                 Token::String(String::from_utf8_lossy(&metadata).into_owned())
-            ])]));
+            ])]))
         },
         SELECTOR_REMOVE_SONG => {
-            let decoded = decode(&[ParamType::Uint(256)], data)
-                .expect("Failed to decode params");
-            let song_id = if let Token::Uint(id) = &decoded[0] {
-                id.as_u32()
-            } else {
-                panic!("Invalid song ID");
-            };
-            remove_song(song_id);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            let decoded = decode_params(&[ParamType::Uint(256)], data)?;
+            let song_id = token_to_u32(&decoded[0])?;
+            remove_song(env, song_id)?;
+            Ok(encode(&[Token::Bool(true)]))
         },
         SELECTOR_REMOVE_SONG_UNIVERSAL => {
-            let decoded = decode(&[ParamType::Address, ParamType::Uint(256)], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            remove_song_universal(env, dj_address, song_id)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_DOWNVOTE | SELECTOR_UNDOWNVOTE => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            if selector == SELECTOR_DOWNVOTE {
+                downvote(env, dj_address, song_id)?;
+            } else {
+                undownvote(env, dj_address, song_id)?;
             }
-            let song_id = if let Token::Uint(id) = &decoded[1] {
-                id.as_u32()
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_HAS_DOWNVOTED => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Address, ParamType::Uint(256)], data)?;
+            let voter = token_to_address(&decoded[0])?;
+            let dj_address = token_to_address(&decoded[1])?;
+            let song_id = token_to_u32(&decoded[2])?;
+            let set_id = get_current_set_id(env, dj_address);
+            Ok(encode(&[Token::Bool(has_downvoted(env, voter, dj_address, set_id, song_id))]))
+        },
+        SELECTOR_GET_SONGS_WITH_SCORES => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let songs = get_songs_with_scores(env, dj_address, set_id);
+            let encoded_songs: Vec<Token> = songs.iter().map(|(id, name, upvotes, downvotes, net_score, hidden)| {
+                Token::Tuple(vec![
+                    Token::Uint((*id).into()),
+                    Token::String(String::from_utf8_lossy(name).into_owned()),
+                    Token::Uint((*upvotes).into()),
+                    Token::Uint((*downvotes).into()),
+                    i64_to_token(*net_score),
+                    Token::Bool(*hidden)
+                ])
+            }).collect();
+            Ok(encode(&[Token::Array(encoded_songs)]))
+        },
+        SELECTOR_SET_AUTO_HIDE => {
+            let decoded = decode_params(&[ParamType::Bool, ParamType::Int(256)], data)?;
+            let enabled = decoded[0].clone().into_bool().ok_or(Error::InvalidParams)?;
+            let threshold = token_to_i64(&decoded[1])?;
+            set_auto_hide(env, enabled, threshold)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GET_AUTO_HIDE => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let (enabled, threshold) = get_auto_hide(env, dj_address);
+            Ok(encode(&[Token::Bool(enabled), i64_to_token(threshold)]))
+        },
+        SELECTOR_SET_GATE => {
+            let decoded = decode_params(&[ParamType::Uint(8), ParamType::Address, ParamType::Uint(256)], data)?;
+            let kind = token_to_u32(&decoded[0])?;
+            let token = token_to_address(&decoded[1])?;
+            let token_id = decoded[2].clone().into_uint().ok_or(Error::InvalidParams)?;
+            set_gate(env, kind, token, token_id)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GET_GATE => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let (kind, token, token_id) = get_gate(env, dj_address);
+            Ok(encode(&[Token::Uint(kind.into()), Token::Address(token.into()), Token::Uint(token_id)]))
+        },
+        SELECTOR_SET_VOTE_TOKEN => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(8), ParamType::Uint(256), ParamType::Uint(256)], data)?;
+            let token = token_to_address(&decoded[0])?;
+            let curve = token_to_u32(&decoded[1])?;
+            let unit = decoded[2].clone().into_uint().ok_or(Error::InvalidParams)?;
+            let cap = token_to_limit(&decoded[3])?;
+            set_vote_token(env, token, curve, unit, cap)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GET_VOTE_TOKEN => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let (token, curve, unit, cap) = get_vote_token(env, dj_address);
+            Ok(encode(&[
+                Token::Address(token.unwrap_or_default().into()),
+                Token::Uint(curve.into()),
+                Token::Uint(unit),
+                Token::Uint(cap.into()),
+            ]))
+        },
+        SELECTOR_GET_VOTE_WEIGHT => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Address], data)?;
+            let voter = token_to_address(&decoded[0])?;
+            let dj_address = token_to_address(&decoded[1])?;
+            Ok(encode(&[Token::Uint(get_vote_weight(env, voter, dj_address)?.into())]))
+        },
+        SELECTOR_SET_QUADRATIC_VOTING => {
+            let decoded = decode_params(&[ParamType::Bool, ParamType::Uint(256)], data)?;
+            let enabled = decoded[0].clone().into_bool().ok_or(Error::InvalidParams)?;
+            let credits = token_to_limit(&decoded[1])?;
+            set_quadratic_voting(env, enabled, credits)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GET_QUADRATIC_VOTING => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let (enabled, credits) = get_quadratic_voting(env, dj_address);
+            Ok(encode(&[Token::Bool(enabled), Token::Uint(credits.into())]))
+        },
+        SELECTOR_CAST_VOTES | SELECTOR_WITHDRAW_VOTES => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256), ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            let count = token_to_u32(&decoded[2])?;
+            let votes = if selector == SELECTOR_CAST_VOTES {
+                cast_votes(env, dj_address, song_id, count)?
             } else {
-                panic!("Invalid song ID");
+                withdraw_votes(env, dj_address, song_id, count)?
             };
-            remove_song_universal(dj_address, song_id);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            Ok(encode(&[Token::Uint(votes.into())]))
         },
-        SELECTOR_SUGGEST_SONG => {
-            let decoded = decode(&[ParamType::Address, ParamType::String], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let song_name = if let Token::String(name) = &decoded[1] {
-                name.clone().into_bytes()
-            } else {
-                panic!("Invalid song name");
+        SELECTOR_GET_REMAINING_CREDITS => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Address], data)?;
+            let voter = token_to_address(&decoded[0])?;
+            let dj_address = token_to_address(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            Ok(encode(&[Token::Uint(get_remaining_credits(env, voter, dj_address, set_id).into())]))
+        },
+        SELECTOR_GET_VOTES_CAST => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Address, ParamType::Uint(256)], data)?;
+            let voter = token_to_address(&decoded[0])?;
+            let dj_address = token_to_address(&decoded[1])?;
+            let song_id = token_to_u32(&decoded[2])?;
+            let set_id = get_current_set_id(env, dj_address);
+            Ok(encode(&[Token::Uint(get_votes_cast(env, voter, dj_address, set_id, song_id).into())]))
+        },
+        SELECTOR_SET_VOTE_BUDGET => {
+            let decoded = decode_params(&[ParamType::Uint(256)], data)?;
+            let budget = token_to_limit(&decoded[0])?;
+            set_vote_budget(env, budget)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GET_VOTE_BUDGET => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            Ok(encode(&[Token::Uint(get_vote_budget(env, dj_address).into())]))
+        },
+        SELECTOR_GET_REMAINING_VOTES => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Address], data)?;
+            let voter = token_to_address(&decoded[0])?;
+            let dj_address = token_to_address(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            // Unlimited reads as type(uint256).max
+            let remaining = get_remaining_votes(env, voter, dj_address, set_id).map(Into::into).unwrap_or(ethabi::Uint::MAX);
+            Ok(encode(&[Token::Uint(remaining)]))
+        },
+        SELECTOR_MERGE_SONGS => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256), ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let from_id = token_to_u32(&decoded[1])?;
+            let into_id = token_to_u32(&decoded[2])?;
+            let votes = merge_songs(env, dj_address, from_id, into_id)?;
+            Ok(encode(&[Token::Uint(votes.into())]))
+        },
+        SELECTOR_GET_MERGED_INTO => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let (merged, into_id) = match get_merged_into(env, dj_address, set_id, song_id) {
+                Some(into_id) => (true, into_id),
+                None => (false, 0),
+            };
+            Ok(encode(&[Token::Bool(merged), Token::Uint(into_id.into())]))
+        },
+        SELECTOR_REMOVE_SONG_FOR => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            remove_song_for(env, dj_address, song_id)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_RETIRE_UNIVERSAL_REMOVER => {
+            retire_universal_remover(env)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_IS_UNIVERSAL_REMOVER_RETIRED => {
+            Ok(encode(&[Token::Bool(is_universal_remover_retired(env))]))
+        },
+        SELECTOR_ADD_DELEGATE => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let delegate = token_to_address(&decoded[0])?;
+            let permissions = token_to_u32(&decoded[1])?;
+            add_delegate(env, delegate, permissions)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_REMOVE_DELEGATE => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let delegate = token_to_address(&decoded[0])?;
+            remove_delegate(env, delegate)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GET_DELEGATES => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let delegates = get_delegates(env, dj_address);
+            let encoded_delegates: Vec<Token> = delegates.iter().map(|(delegate, permissions)| {
+                Token::Tuple(vec![
+                    Token::Address((*delegate).into()),
+                    Token::Uint((*permissions).into())
+                ])
+            }).collect();
+            Ok(encode(&[Token::Array(encoded_delegates)]))
+        },
+        SELECTOR_ADD_SONG_DETAILED => {
+            let decoded = decode_params(&[ParamType::String, ParamType::String, ParamType::String, ParamType::Uint(256)], data)?;
+            let title = token_to_bytes(&decoded[0])?;
+            let artist = token_to_bytes(&decoded[1])?;
+            let source_uri = token_to_bytes(&decoded[2])?;
+            let duration = token_to_u32(&decoded[3])?;
+            let song_id = add_song_detailed(env, title, artist, source_uri, duration)?;
+            Ok(encode(&[Token::Uint(song_id.into())]))
+        },
+        SELECTOR_SUGGEST_SONG_DETAILED => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::String, ParamType::String, ParamType::String, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let title = token_to_bytes(&decoded[1])?;
+            let artist = token_to_bytes(&decoded[2])?;
+            let source_uri = token_to_bytes(&decoded[3])?;
+            let duration = token_to_u32(&decoded[4])?;
+            let song_id = suggest_song_detailed(env, dj_address, title, artist, source_uri, duration)?;
+            Ok(encode(&[Token::Uint(song_id.into())]))
+        },
+        SELECTOR_SET_REJECT_DUPLICATES => {
+            let decoded = decode_params(&[ParamType::Bool], data)?;
+            let reject = decoded[0].clone().into_bool().ok_or(Error::InvalidParams)?;
+            set_reject_duplicates(env, reject)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_REJECTS_DUPLICATES => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            Ok(encode(&[Token::Bool(rejects_duplicates(env, dj_address))]))
+        },
+        SELECTOR_FIND_SONG => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::String, ParamType::String], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_name = token_to_bytes(&decoded[1])?;
+            let source_uri = token_to_bytes(&decoded[2])?;
+            let (found, song_id) = match find_song(env, dj_address, &song_name, &source_uri) {
+                Some(song_id) => (true, song_id),
+                None => (false, 0),
             };
-            let song_id = suggest_song(dj_address, song_name);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Uint(song_id.into())]));
+            Ok(encode(&[Token::Bool(found), Token::Uint(song_id.into())]))
+        },
+        SELECTOR_SET_SUGGESTION_LIMITS => {
+            let decoded = decode_params(&[ParamType::Uint(256), ParamType::Uint(256)], data)?;
+            let max_per_set = token_to_limit(&decoded[0])?;
+            let min_interval = token_to_u64(&decoded[1])?;
+            set_suggestion_limits(env, max_per_set, min_interval)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GET_SUGGESTION_LIMITS => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let (max_per_set, min_interval) = get_suggestion_limits(env, dj_address);
+            Ok(encode(&[Token::Uint(max_per_set.into()), Token::Uint(min_interval.into())]))
+        },
+        SELECTOR_GET_SUGGESTION_ALLOWANCE => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let suggester = token_to_address(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let (remaining, next_allowed_at) = get_suggestion_allowance(env, dj_address, set_id, suggester);
+            // Unlimited reads as type(uint256).max
+            let remaining = remaining.map(Into::into).unwrap_or(ethabi::Uint::MAX);
+            Ok(encode(&[Token::Uint(remaining), Token::Uint(next_allowed_at.into())]))
+        },
+        SELECTOR_SET_SUGGESTION_APPROVAL => {
+            let decoded = decode_params(&[ParamType::Bool], data)?;
+            let required = decoded[0].clone().into_bool().ok_or(Error::InvalidParams)?;
+            set_suggestion_approval(env, required)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_REQUIRES_SUGGESTION_APPROVAL => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            Ok(encode(&[Token::Bool(requires_suggestion_approval(env, dj_address))]))
+        },
+        SELECTOR_APPROVE_SUGGESTION | SELECTOR_REJECT_SUGGESTION => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            review_suggestion(env, dj_address, song_id, selector == SELECTOR_APPROVE_SUGGESTION)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GET_PENDING_SUGGESTIONS => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let songs = get_pending_suggestions(env, dj_address, set_id);
+            Ok(encode(&[encode_songs_with_votes(&songs)]))
+        },
+        SELECTOR_GET_SONG_ORIGIN => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let (added_by, added_at, suggested) = get_song_origin(env, dj_address, set_id, song_id)?;
+            Ok(encode(&[Token::Tuple(vec![
+                Token::Address(added_by.into()),
+                Token::Uint(added_at.into()),
+                Token::Bool(suggested)
+            ])]))
+        },
+        SELECTOR_GET_SONGS_BY_SUGGESTER => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let suggester = token_to_address(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let song_ids = get_songs_by_suggester(env, dj_address, set_id, suggester);
+            let ids: Vec<Token> = song_ids.iter().map(|id| Token::Uint((*id).into())).collect();
+            Ok(encode(&[Token::Array(ids)]))
+        },
+        SELECTOR_GET_SONG_DETAILS => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let (title, artist, source_uri, duration, added_by, added_at) = get_song_details(env, dj_address, set_id, song_id)?;
+            Ok(encode(&[Token::Tuple(vec![
+                Token::String(String::from_utf8_lossy(&title).into_owned()),
+                Token::String(String::from_utf8_lossy(&artist).into_owned()),
+                Token::String(String::from_utf8_lossy(&source_uri).into_owned()),
+                Token::Uint(duration.into()),
+                Token::Address(added_by.into()),
+                Token::Uint(added_at.into())
+            ])]))
+        },
+        SELECTOR_SUGGEST_SONG => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::String], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_name = token_to_bytes(&decoded[1])?;
+            let song_id = suggest_song(env, dj_address, song_name)?;
+            Ok(encode(&[Token::Uint(song_id.into())]))
         },
         SELECTOR_GET_ALL_SONGS_WITH_VOTES => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let songs = get_all_songs_with_votes(dj_address);
-            let encoded_songs: Vec<Token> = songs.iter().map(|(id, name, votes)| {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let songs = get_all_songs_with_votes(env, dj_address, set_id);
+            Ok(encode(&[encode_songs_with_votes(&songs)]))
+        },
+        SELECTOR_GET_SONGS_WITH_VOTES_PAGED => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256), ParamType::Uint(256), ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = token_to_u32(&decoded[1])?;
+            let offset = token_to_limit(&decoded[2])?;
+            let limit = token_to_limit(&decoded[3])?.min(MAX_PAGE_SIZE);
+            let (songs, total) = get_songs_with_votes_page(env, dj_address, set_id, offset, limit);
+            Ok(encode(&[encode_songs_with_votes(&songs), Token::Uint(total.into())]))
+        },
+        SELECTOR_GET_SONGS_WITH_VOTES_FROM => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256), ParamType::Uint(256), ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = token_to_u32(&decoded[1])?;
+            let cursor = token_to_limit(&decoded[2])?;
+            let limit = token_to_limit(&decoded[3])?.min(MAX_PAGE_SIZE);
+            let (songs, next_cursor) = get_songs_with_votes_from(env, dj_address, set_id, cursor, limit);
+            let has_more = next_cursor < get_song_count(env, dj_address, set_id);
+            Ok(encode(&[encode_songs_with_votes(&songs), Token::Uint(next_cursor.into()), Token::Bool(has_more)]))
+        },
+        SELECTOR_GET_SET_SONGS_WITH_VOTES => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = token_to_u32(&decoded[1])?;
+            let songs = get_all_songs_with_votes(env, dj_address, set_id);
+            Ok(encode(&[encode_songs_with_votes(&songs)]))
+        },
+        SELECTOR_GET_SET_SONG_COUNT => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = token_to_u32(&decoded[1])?;
+            let count = get_song_count(env, dj_address, set_id);
+            Ok(encode(&[Token::Uint(count.into())]))
+        },
+        SELECTOR_HAS_VOTED_IN_SET => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Address, ParamType::Uint(256), ParamType::Uint(256)], data)?;
+            let voter = token_to_address(&decoded[0])?;
+            let dj_address = token_to_address(&decoded[1])?;
+            let set_id = token_to_u32(&decoded[2])?;
+            let song_id = token_to_u32(&decoded[3])?;
+            let voted = has_voted(env, voter, dj_address, set_id, song_id);
+            Ok(encode(&[Token::Bool(voted)]))
+        },
+        SELECTOR_GET_TOP_SONGS => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let n = token_to_limit(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let songs = get_top_songs(env, dj_address, set_id, n);
+            Ok(encode(&[encode_songs_with_votes(&songs)]))
+        },
+        SELECTOR_GET_TOP_SONGS_WITH_SCORES => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let n = token_to_limit(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let songs = get_top_songs_with_scores(env, dj_address, set_id, n);
+            let encoded_songs: Vec<Token> = songs.iter().map(|(id, name, upvotes, downvotes, score)| {
                 Token::Tuple(vec![
                     Token::Uint((*id).into()),
                     Token::String(String::from_utf8_lossy(name).into_owned()),
-                    Token::Uint((*votes).into())
+                    Token::Uint((*upvotes).into()),
+                    Token::Uint((*downvotes).into()),
+                    i64_to_token(*score)
                 ])
             }).collect();
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Array(encoded_songs)]));
+            Ok(encode(&[Token::Array(encoded_songs)]))
+        },
+        SELECTOR_SET_VOTE_DECAY => {
+            let decoded = decode_params(&[ParamType::Uint(256)], data)?;
+            let half_life = token_to_u64(&decoded[0])?;
+            set_vote_decay(env, half_life)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_GET_VOTE_DECAY => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            Ok(encode(&[Token::Uint(get_vote_decay(env, dj_address).into())]))
+        },
+        SELECTOR_GET_QUEUE_POSITION => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let position = get_queue_position(env, dj_address, set_id, song_id)?;
+            Ok(encode(&[Token::Uint(position.into())]))
+        },
+        SELECTOR_ADVANCE => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = advance(env, dj_address)?;
+            Ok(encode(&[Token::Uint(song_id.into())]))
+        },
+        SELECTOR_MARK_PLAYED => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            mark_played(env, dj_address, song_id)?;
+            Ok(encode(&[Token::Bool(true)]))
+        },
+        SELECTOR_NOW_PLAYING => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let (is_playing, song_id, name, played_at) = match now_playing(env, dj_address) {
+                Some((song_id, name, played_at)) => (true, song_id, name, played_at),
+                None => (false, 0, Vec::new(), 0),
+            };
+            Ok(encode(&[Token::Tuple(vec![
+                Token::Bool(is_playing),
+                Token::Uint(song_id.into()),
+                Token::String(String::from_utf8_lossy(&name).into_owned()),
+                Token::Uint(played_at.into())
+            ])]))
+        },
+        SELECTOR_GET_PLAY_HISTORY => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256), ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let offset = token_to_limit(&decoded[1])?;
+            let limit = token_to_limit(&decoded[2])?.min(MAX_PAGE_SIZE);
+            let set_id = get_current_set_id(env, dj_address);
+            let history = get_play_history(env, dj_address, set_id, offset, limit);
+            let encoded_history: Vec<Token> = history.iter().map(|(id, name, votes, played_at)| {
+                Token::Tuple(vec![
+                    Token::Uint((*id).into()),
+                    Token::String(String::from_utf8_lossy(name).into_owned()),
+                    Token::Uint((*votes).into()),
+                    Token::Uint((*played_at).into())
+                ])
+            }).collect();
+            Ok(encode(&[Token::Array(encoded_history)]))
+        },
+        SELECTOR_GET_PLAYED_AT => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let played_at = get_played_at(env, dj_address, set_id, song_id);
+            Ok(encode(&[Token::Uint(played_at.into())]))
+        },
+        SELECTOR_GET_CURRENT_SET_ID => {
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = get_current_set_id(env, dj_address);
+            Ok(encode(&[Token::Uint(set_id.into())]))
+        },
+        SELECTOR_GET_SET_INFO => {
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let set_id = token_to_u32(&decoded[1])?;
+            let (start_time, end_time, song_count) = get_set_info(env, dj_address, set_id);
+            Ok(encode(&[Token::Tuple(vec![
+                Token::Uint(start_time.into()),
+                Token::Uint(end_time.into()),
+                Token::Uint(song_count.into())
+            ])]))
         },
         SELECTOR_GET_DJ_INFO_EXTENDED => {
-            let decoded = decode(&[ParamType::Address], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let (is_registered, is_active, start_time, end_time, song_count, metadata) = get_dj_info_extended(dj_address);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Tuple(vec![
+            let decoded = decode_params(&[ParamType::Address], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let (is_registered, is_active, start_time, end_time, song_count, metadata) = get_dj_info_extended(env, dj_address);
+            Ok(encode(&[Token::Tuple(vec![
                 Token::Bool(is_registered),
                 Token::Bool(is_active),
                 Token::Uint(start_time.into()),
                 Token::Uint(end_time.into()),
                 Token::Uint(song_count.into()),
                 Token::String(String::from_utf8_lossy(&metadata).into_owned())
-            ])]));
+            ])]))
         },
         SELECTOR_UNVOTE => {
-            let decoded = decode(&[ParamType::Address, ParamType::Uint(256)], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let song_id = if let Token::Uint(id) = &decoded[1] {
-                id.as_u32()
-            } else {
-                panic!("Invalid song ID");
-            };
-            unvote(dj_address, song_id);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(true)]));
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            unvote(env, dj_address, song_id)?;
+            Ok(encode(&[Token::Bool(true)]))
         },
         SELECTOR_IS_SONG_REMOVED => {
-            let decoded = decode(&[ParamType::Address, ParamType::Uint(256)], data)
-                .expect("Failed to decode params");
-            let mut dj_address = [0u8; 20];
-            if let Token::Address(addr) = &decoded[0] {
-                dj_address.copy_from_slice(&addr.0);
-            }
-            let song_id = if let Token::Uint(id) = &decoded[1] {
-                id.as_u32()
-            } else {
-                panic!("Invalid song ID");
-            };
-            let is_removed = is_song_removed(dj_address, song_id);
-            api::return_value(ReturnFlags::empty(), &encode(&[Token::Bool(is_removed)]));
+            let decoded = decode_params(&[ParamType::Address, ParamType::Uint(256)], data)?;
+            let dj_address = token_to_address(&decoded[0])?;
+            let song_id = token_to_u32(&decoded[1])?;
+            let set_id = get_current_set_id(env, dj_address);
+            let is_removed = is_song_removed(env, dj_address, set_id, song_id);
+            Ok(encode(&[Token::Bool(is_removed)]))
         },
         _ => {
             // Unknown selector - handle as fallback
-            api::return_value(ReturnFlags::empty(), &[]);
+            Ok(Vec::new())
         }
     }
 }
 
+// Deployer becomes the contract owner
+fn initialize(env: &mut impl Env) {
+    let origin = env.caller();
+    save_address(env, &KEY_OWNER, &origin);
+    save_u32(env, &get_schema_version_key(), CURRENT_SCHEMA_VERSION);
+    
+    emit_event(env, &[EVENT_OWNERSHIP_TRANSFERRED, address_topic(&[0u8; 20]), address_topic(&origin)], &[]);
+}
+
+// Abort the call and hand the error selector back to the caller as revert data
+#[cfg(not(test))]
+fn revert(error: Error) -> ! {
+    api::return_value(ReturnFlags::REVERT, &error.selector())
+}
+
+#[cfg(not(test))]
 #[no_mangle]
 #[polkavm_export]
 pub extern "C" fn deploy() {
-    let origin = get_origin();
-    save_address(&KEY_OWNER, &origin);
+    initialize(&mut env::HostEnv);
 }
 
+#[cfg(not(test))]
 #[no_mangle]
 #[polkavm_export]
 pub extern "C" fn call() {
@@ -946,7 +3981,7 @@ pub extern "C" fn call() {
         api::return_value(ReturnFlags::empty(), &[]);
     }
     if length < 4 {
-        api::return_value(ReturnFlags::REVERT, b"Invalid input");
+        revert(Error::InvalidInput);
     }
     
     let mut selector = [0u8; 4];
@@ -958,13 +3993,17 @@ pub extern "C" fn call() {
         api::call_data_copy(&mut data, 4);
     }
     
-    dispatch(selector, &data);
+    match dispatch(&mut env::HostEnv, selector, &data) {
+        Ok(output) => api::return_value(ReturnFlags::empty(), &output),
+        Err(error) => revert(error),
+    }
 }
 
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
     unsafe {
         core::arch::asm!("unimp");
         core::hint::unreachable_unchecked();
     }
-}
\ No newline at end of file
+}
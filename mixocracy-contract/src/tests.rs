@@ -0,0 +1,1194 @@
+use super::*;
+use crate::env::MockEnv;
+
+const OWNER: [u8; 20] = [1u8; 20];
+const DJ: [u8; 20] = [2u8; 20];
+const VOTER: [u8; 20] = [3u8; 20];
+const OTHER: [u8; 20] = [4u8; 20];
+
+// Deployed contract with OWNER as owner and DJ registered
+fn setup() -> MockEnv {
+    let mut env = MockEnv::new();
+    env.set_caller(OWNER);
+    initialize(&mut env);
+    register_dj(&mut env, DJ).unwrap();
+    env
+}
+
+// DJ is live in set 1 with two songs in the queue
+fn setup_live_set() -> MockEnv {
+    let mut env = setup();
+    env.set_caller(DJ);
+    env.set_now(1_000);
+    start_set(&mut env, DJ).unwrap();
+    add_song(&mut env, b"Artist - First".to_vec()).unwrap();
+    add_song(&mut env, b"Artist - Second".to_vec()).unwrap();
+    env
+}
+
+fn call(env: &mut MockEnv, selector: [u8; 4], params: &[Token]) -> Result<Vec<u8>, Error> {
+    dispatch(env, selector, &encode(params))
+}
+
+#[test]
+fn owner_registers_and_removes_djs() {
+    let mut env = setup();
+    assert!(is_dj(&env, DJ));
+    assert_eq!(get_all_djs(&env), vec![DJ]);
+
+    // Registering twice doesn't duplicate the roster entry
+    register_dj(&mut env, DJ).unwrap();
+    assert_eq!(get_all_djs(&env), vec![DJ]);
+    assert_eq!(env.events_with_topic(EVENT_DJ_REGISTERED).len(), 1);
+
+    remove_dj(&mut env, DJ).unwrap();
+    assert!(!is_dj(&env, DJ));
+    assert_eq!(env.events_with_topic(EVENT_DJ_REMOVED).len(), 1);
+}
+
+#[test]
+fn only_owner_can_register() {
+    let mut env = setup();
+    env.set_caller(OTHER);
+    assert_eq!(register_dj(&mut env, OTHER), Err(Error::MissingRole));
+    assert_eq!(remove_dj(&mut env, DJ), Err(Error::MissingRole));
+    assert!(!is_dj(&env, OTHER));
+}
+
+#[test]
+fn dj_adds_songs() {
+    let mut env = setup();
+    env.set_caller(DJ);
+    assert_eq!(add_song(&mut env, b"Artist - Title".to_vec()), Ok(0));
+    assert_eq!(add_song(&mut env, b"Artist - Other".to_vec()), Ok(1));
+    // Songs queued before the first set live in set 0
+    assert_eq!(get_song_count(&env, DJ, 0), 2);
+    assert_eq!(get_song(&env, DJ, 0, 0), b"Artist - Title".to_vec());
+
+    assert_eq!(add_song(&mut env, Vec::new()), Err(Error::EmptySongName));
+    assert_eq!(add_song(&mut env, vec![b'a'; 257]), Err(Error::SongNameTooLong));
+
+    env.set_caller(VOTER);
+    assert_eq!(add_song(&mut env, b"Nope".to_vec()), Err(Error::NotDj));
+}
+
+#[test]
+fn suggestions_require_a_live_set() {
+    let mut env = setup();
+    env.set_caller(VOTER);
+    assert_eq!(suggest_song(&mut env, DJ, b"Request".to_vec()), Err(Error::DjNotActive));
+    assert_eq!(suggest_song(&mut env, OTHER, b"Request".to_vec()), Err(Error::TargetNotDj));
+
+    env.set_caller(DJ);
+    start_set(&mut env, DJ).unwrap();
+    env.set_caller(VOTER);
+    assert_eq!(suggest_song(&mut env, DJ, b"Request".to_vec()), Ok(0));
+    assert_eq!(get_song(&env, DJ, 1, 0), b"Request".to_vec());
+
+    let events = env.events_with_topic(EVENT_SONG_SUGGESTED);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].0[3], address_topic(&VOTER));
+}
+
+#[test]
+fn vote_and_unvote() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 1), 1);
+    assert!(has_voted(&env, VOTER, DJ, 1, 1));
+    assert_eq!(vote(&mut env, DJ, 1), Err(Error::AlreadyVoted));
+
+    env.set_caller(OTHER);
+    vote(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 1), 2);
+
+    unvote(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 1), 1);
+    assert!(!has_voted(&env, OTHER, DJ, 1, 1));
+    assert_eq!(unvote(&mut env, DJ, 1), Err(Error::NotVoted));
+
+    assert_eq!(env.events_with_topic(EVENT_VOTED).len(), 2);
+    assert_eq!(env.events_with_topic(EVENT_UNVOTED).len(), 1);
+}
+
+#[test]
+fn voting_requires_live_set_and_existing_song() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    assert_eq!(vote(&mut env, DJ, 7), Err(Error::SongNotFound));
+
+    env.set_caller(DJ);
+    stop_set(&mut env, DJ).unwrap();
+    env.set_caller(VOTER);
+    assert_eq!(vote(&mut env, DJ, 0), Err(Error::SetNotActive));
+    assert_eq!(unvote(&mut env, DJ, 0), Err(Error::SetNotActive));
+}
+
+#[test]
+fn start_and_stop_set() {
+    let mut env = setup();
+    env.set_caller(OTHER);
+    assert_eq!(start_set(&mut env, DJ), Err(Error::Unauthorized));
+
+    env.set_caller(DJ);
+    env.set_now(1_000);
+    start_set(&mut env, DJ).unwrap();
+    assert!(is_set_active(&env, DJ));
+    assert_eq!(get_active_djs(&env), vec![DJ]);
+    assert_eq!(start_set(&mut env, DJ), Err(Error::SetAlreadyActive));
+
+    env.set_now(5_000);
+    stop_set(&mut env, DJ).unwrap();
+    assert!(!is_set_active(&env, DJ));
+    assert!(get_active_djs(&env).is_empty());
+    assert_eq!(stop_set(&mut env, DJ), Err(Error::SetNotActive));
+
+    let (_, is_active, start_time, end_time, _, _) = get_dj_info_extended(&env, DJ);
+    assert!(!is_active);
+    assert_eq!((start_time, end_time), (1_000, 5_000));
+}
+
+#[test]
+fn owner_can_run_a_djs_set() {
+    let mut env = setup();
+    start_set(&mut env, DJ).unwrap();
+    assert!(is_set_active(&env, DJ));
+    stop_set(&mut env, DJ).unwrap();
+    assert!(!is_set_active(&env, DJ));
+}
+
+#[test]
+fn removed_songs_are_hidden_and_unvotable() {
+    let mut env = setup_live_set();
+    env.set_caller(DJ);
+    remove_song(&mut env, 0).unwrap();
+    assert!(is_song_removed(&env, DJ, 1, 0));
+    assert_eq!(remove_song(&mut env, 9), Err(Error::SongNotFound));
+
+    let songs = get_all_songs_with_votes(&env, DJ, 1);
+    assert_eq!(songs.len(), 1);
+    assert_eq!(songs[0].0, 1);
+
+    env.set_caller(VOTER);
+    assert_eq!(vote(&mut env, DJ, 0), Err(Error::SongWasRemoved));
+    assert_eq!(env.events_with_topic(EVENT_SONG_REMOVED).len(), 1);
+}
+
+#[test]
+fn universal_remover_requires_a_dj() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    assert_eq!(remove_song_universal(&mut env, DJ, 0), Err(Error::NotDj));
+
+    env.set_caller(OWNER);
+    register_dj(&mut env, OTHER).unwrap();
+    env.set_caller(OTHER);
+    remove_song_universal(&mut env, DJ, 0).unwrap();
+    assert!(is_song_removed(&env, DJ, 1, 0));
+}
+
+#[test]
+fn owner_clears_votes() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+    assert_eq!(clear_votes(&mut env, DJ, 0), Err(Error::MissingRole));
+
+    env.set_caller(OWNER);
+    clear_votes(&mut env, DJ, 0).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 0), 0);
+}
+
+#[test]
+fn dispatch_decodes_calls_and_surfaces_errors() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    let params = [Token::Address(DJ.into()), Token::Uint(1u32.into())];
+    assert_eq!(call(&mut env, SELECTOR_VOTE, &params), Ok(encode(&[Token::Bool(true)])));
+    assert_eq!(call(&mut env, SELECTOR_VOTE, &params), Err(Error::AlreadyVoted));
+    assert_eq!(
+        call(&mut env, SELECTOR_GET_VOTES, &params),
+        Ok(encode(&[Token::Uint(1u32.into())]))
+    );
+
+    // Malformed calldata and out-of-range IDs revert instead of trapping
+    assert_eq!(dispatch(&mut env, SELECTOR_VOTE, &[0u8; 3]), Err(Error::InvalidParams));
+    let too_big = [Token::Address(DJ.into()), Token::Uint(u64::MAX.into())];
+    assert_eq!(call(&mut env, SELECTOR_VOTE, &too_big), Err(Error::InvalidParams));
+}
+
+#[test]
+fn errors_revert_with_custom_error_selectors() {
+    // keccak256("AlreadyVoted()")[..4]
+    assert_eq!(Error::AlreadyVoted.selector(), [0x7c, 0x9a, 0x1c, 0xf9]);
+    assert_ne!(Error::NotOwner.selector(), Error::NotDj.selector());
+}
+
+#[test]
+fn each_set_starts_with_a_fresh_queue() {
+    let mut env = setup_live_set();
+    assert_eq!(get_current_set_id(&env, DJ), 1);
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+
+    env.set_caller(DJ);
+    env.set_now(2_000);
+    stop_set(&mut env, DJ).unwrap();
+    env.set_now(3_000);
+    start_set(&mut env, DJ).unwrap();
+    assert_eq!(get_current_set_id(&env, DJ), 2);
+    assert_eq!(get_song_count(&env, DJ, 2), 0);
+    add_song(&mut env, b"New night".to_vec()).unwrap();
+
+    // Song 0 of the new set is a different song with its own votes and voter flags
+    assert_eq!(get_votes(&env, DJ, 2, 0), 0);
+    assert!(!has_voted(&env, VOTER, DJ, 2, 0));
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+
+    // The previous set stays readable as history
+    let history = get_all_songs_with_votes(&env, DJ, 1);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0], (0, b"Artist - First".to_vec(), 1));
+    assert!(has_voted(&env, VOTER, DJ, 1, 0));
+    assert_eq!(get_set_info(&env, DJ, 1), (1_000, 2_000, 2));
+    assert_eq!(get_set_info(&env, DJ, 2), (3_000, 0, 1));
+}
+
+#[test]
+fn set_zero_reads_the_pre_set_layout() {
+    // Storage written before sets existed used keys without a set ID
+    let mut env = setup();
+    let mut song_key = [0u8; 32];
+    song_key[0] = PREFIX_SONG;
+    song_key[1..21].copy_from_slice(&DJ);
+    env.set_storage(&song_key, b"Legacy track");
+    let mut count_key = [0u8; 32];
+    count_key[0] = PREFIX_SONG_COUNT;
+    count_key[1..21].copy_from_slice(&DJ);
+    env.set_storage(&count_key, &1u32.to_le_bytes());
+
+    assert_eq!(get_current_set_id(&env, DJ), 0);
+    assert_eq!(get_all_songs_with_votes(&env, DJ, 0), vec![(0, b"Legacy track".to_vec(), 0)]);
+}
+
+#[test]
+fn top_songs_rank_by_votes_then_earliest_id() {
+    let mut env = setup_live_set();
+    add_song(&mut env, b"Artist - Third".to_vec()).unwrap();
+    remove_song(&mut env, 0).unwrap();
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 2).unwrap();
+
+    let top = get_top_songs(&env, DJ, 1, 10);
+    assert_eq!(top.iter().map(|song| song.0).collect::<Vec<_>>(), vec![2, 1]);
+    assert_eq!(top[0], (2, b"Artist - Third".to_vec(), 1));
+    assert_eq!(get_top_songs(&env, DJ, 1, 1).len(), 1);
+
+    // Tied songs keep queue order
+    env.set_caller(OTHER);
+    vote(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_top_songs(&env, DJ, 1, 2)[0].0, 1);
+    assert_eq!(get_queue_position(&env, DJ, 1, 1), Ok(1));
+    assert_eq!(get_queue_position(&env, DJ, 1, 2), Ok(2));
+    assert_eq!(get_queue_position(&env, DJ, 1, 0), Err(Error::SongWasRemoved));
+    assert_eq!(get_queue_position(&env, DJ, 1, 5), Err(Error::SongNotFound));
+
+    // Oversized n is clamped rather than rejected
+    let params = [Token::Address(DJ.into()), Token::Uint(ethabi::Uint::MAX)];
+    assert!(call(&mut env, SELECTOR_GET_TOP_SONGS, &params).is_ok());
+}
+
+#[test]
+fn advance_plays_the_top_song() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 1).unwrap();
+    assert_eq!(now_playing(&env, DJ), None);
+
+    env.set_caller(DJ);
+    env.set_now(1_500);
+    assert_eq!(advance(&mut env, DJ), Ok(1));
+    assert_eq!(now_playing(&env, DJ), Some((1, b"Artist - Second".to_vec(), 1_500)));
+    assert_eq!(get_played_at(&env, DJ, 1, 1), 1_500);
+
+    // Played is distinct from removed, and the song leaves the queue
+    assert!(!is_song_removed(&env, DJ, 1, 1));
+    assert_eq!(get_all_songs_with_votes(&env, DJ, 1).len(), 1);
+    env.set_caller(OTHER);
+    assert_eq!(vote(&mut env, DJ, 1), Err(Error::SongAlreadyPlayed));
+    assert_eq!(advance(&mut env, DJ), Err(Error::Unauthorized));
+
+    env.set_caller(DJ);
+    env.set_now(1_800);
+    assert_eq!(advance(&mut env, DJ), Ok(0));
+    assert_eq!(advance(&mut env, DJ), Err(Error::QueueEmpty));
+    env.set_caller(OTHER);
+    assert_eq!(advance(&mut env, DJ), Err(Error::Unauthorized));
+    env.set_caller(DJ);
+    assert_eq!(env.events_with_topic(EVENT_SONG_PLAYED).len(), 2);
+
+    let history = get_play_history(&env, DJ, 1, 0, 10);
+    assert_eq!(history, vec![
+        (1, b"Artist - Second".to_vec(), 1, 1_500),
+        (0, b"Artist - First".to_vec(), 0, 1_800),
+    ]);
+    assert_eq!(get_play_history(&env, DJ, 1, 1, 10).len(), 1);
+
+    // Nothing is playing once the set ends, but the history remains
+    stop_set(&mut env, DJ).unwrap();
+    assert_eq!(now_playing(&env, DJ), None);
+    assert_eq!(get_play_history(&env, DJ, 1, 0, 10).len(), 2);
+}
+
+#[test]
+fn mark_played_picks_a_specific_song() {
+    let mut env = setup_live_set();
+    mark_played(&mut env, DJ, 0).unwrap();
+    assert_eq!(mark_played(&mut env, DJ, 0), Err(Error::SongAlreadyPlayed));
+    assert_eq!(mark_played(&mut env, DJ, 9), Err(Error::SongNotFound));
+    remove_song(&mut env, 1).unwrap();
+    assert_eq!(mark_played(&mut env, DJ, 1), Err(Error::SongWasRemoved));
+}
+
+#[test]
+fn delegates_act_only_within_their_permissions() {
+    let mut env = setup_live_set();
+    let hot_wallet = OTHER;
+
+    // Delegates are scoped to the caller's own room
+    env.set_caller(VOTER);
+    assert_eq!(add_delegate(&mut env, hot_wallet, PERMISSION_REMOVE_SONG), Err(Error::NotDj));
+    env.set_caller(DJ);
+    assert_eq!(add_delegate(&mut env, hot_wallet, 0), Err(Error::InvalidParams));
+    assert_eq!(add_delegate(&mut env, hot_wallet, 1 << 4), Err(Error::InvalidParams));
+    add_delegate(&mut env, hot_wallet, PERMISSION_REMOVE_SONG | PERMISSION_MARK_PLAYED).unwrap();
+    add_delegate(&mut env, VOTER, PERMISSION_START_STOP_SET).unwrap();
+    assert_eq!(get_delegates(&env, DJ), vec![(hot_wallet, 3), (VOTER, PERMISSION_START_STOP_SET)]);
+
+    env.set_caller(hot_wallet);
+    remove_song_for(&mut env, DJ, 0).unwrap();
+    assert_eq!(advance(&mut env, DJ), Ok(1));
+    assert_eq!(stop_set(&mut env, DJ), Err(Error::Unauthorized));
+    env.set_caller(VOTER);
+    assert_eq!(remove_song_for(&mut env, DJ, 1), Err(Error::Unauthorized));
+    stop_set(&mut env, DJ).unwrap();
+
+    // Updating permissions keeps a single entry; removal revokes access
+    env.set_caller(DJ);
+    add_delegate(&mut env, hot_wallet, PERMISSION_START_STOP_SET).unwrap();
+    assert_eq!(get_delegates(&env, DJ).len(), 2);
+    remove_delegate(&mut env, VOTER).unwrap();
+    assert_eq!(remove_delegate(&mut env, VOTER), Err(Error::NotDelegate));
+    assert_eq!(get_delegates(&env, DJ), vec![(hot_wallet, PERMISSION_START_STOP_SET)]);
+    env.set_caller(VOTER);
+    assert_eq!(start_set(&mut env, DJ), Err(Error::Unauthorized));
+    assert_eq!(env.events_with_topic(EVENT_DELEGATE_UPDATED).len(), 3);
+    assert_eq!(env.events_with_topic(EVENT_DELEGATE_REMOVED).len(), 1);
+}
+
+#[test]
+fn owner_can_retire_the_universal_remover() {
+    let mut env = setup_live_set();
+    env.set_caller(DJ);
+    assert_eq!(retire_universal_remover(&mut env), Err(Error::NotOwner));
+
+    env.set_caller(OWNER);
+    retire_universal_remover(&mut env).unwrap();
+    assert!(is_universal_remover_retired(&env));
+
+    env.set_caller(DJ);
+    assert_eq!(remove_song_universal(&mut env, DJ, 0), Err(Error::UniversalRemoverIsRetired));
+    remove_song_for(&mut env, DJ, 0).unwrap();
+    assert!(is_song_removed(&env, DJ, 1, 0));
+}
+
+#[test]
+fn ownership_moves_in_two_steps() {
+    let mut env = setup();
+    let new_owner = OTHER;
+
+    env.set_caller(DJ);
+    assert_eq!(transfer_ownership(&mut env, DJ), Err(Error::NotOwner));
+
+    env.set_caller(OWNER);
+    transfer_ownership(&mut env, new_owner).unwrap();
+    assert_eq!(get_pending_owner(&env), new_owner);
+    assert_eq!(get_owner(&env), OWNER);
+
+    env.set_caller(VOTER);
+    assert_eq!(accept_ownership(&mut env), Err(Error::NotPendingOwner));
+    env.set_caller(new_owner);
+    accept_ownership(&mut env).unwrap();
+    assert_eq!(get_owner(&env), new_owner);
+    assert_eq!(get_pending_owner(&env), [0u8; 20]);
+    register_dj(&mut env, VOTER).unwrap();
+
+    env.set_caller(OWNER);
+    assert_eq!(register_dj(&mut env, VOTER), Err(Error::MissingRole));
+
+    // Deploy and accept both show up as OwnershipTransferred
+    let transfers = env.events_with_topic(EVENT_OWNERSHIP_TRANSFERRED);
+    assert_eq!(transfers.len(), 2);
+    assert_eq!(transfers[1].0[1], address_topic(&OWNER));
+    assert_eq!(transfers[1].0[2], address_topic(&new_owner));
+}
+
+#[test]
+fn renounced_ownership_cannot_be_reclaimed() {
+    let mut env = setup();
+    transfer_ownership(&mut env, OTHER).unwrap();
+    renounce_ownership(&mut env).unwrap();
+    assert_eq!(get_owner(&env), [0u8; 20]);
+
+    // The pending nomination is dropped along with ownership
+    env.set_caller(OTHER);
+    assert_eq!(accept_ownership(&mut env), Err(Error::NotPendingOwner));
+    env.set_caller(OWNER);
+    assert_eq!(register_dj(&mut env, VOTER), Err(Error::MissingRole));
+}
+
+#[test]
+fn roles_gate_staff_actions() {
+    let mut env = setup_live_set();
+    let manager = VOTER;
+    let moderator = OTHER;
+
+    env.set_caller(DJ);
+    assert_eq!(grant_role(&mut env, ROLE_DJ_MANAGER, manager), Err(Error::MissingRole));
+    assert_eq!(grant_role(&mut env, [7u8; 32], manager), Err(Error::UnknownRole));
+
+    env.set_caller(OWNER);
+    grant_role(&mut env, ROLE_DJ_MANAGER, manager).unwrap();
+    grant_role(&mut env, ROLE_MODERATOR, moderator).unwrap();
+    assert_eq!(has_role(&env, &ROLE_DJ_MANAGER, manager), Ok(true));
+    assert_eq!(has_role(&env, &ROLE_MODERATOR, manager), Ok(false));
+    assert_eq!(get_role_members(&env, &ROLE_MODERATOR), Ok(vec![moderator]));
+
+    // Each role only unlocks its own actions
+    env.set_caller(manager);
+    register_dj(&mut env, [5u8; 20]).unwrap();
+    remove_dj(&mut env, [5u8; 20]).unwrap();
+    assert_eq!(clear_votes(&mut env, DJ, 0), Err(Error::MissingRole));
+    env.set_caller(moderator);
+    clear_votes(&mut env, DJ, 0).unwrap();
+    assert_eq!(register_dj(&mut env, [5u8; 20]), Err(Error::MissingRole));
+
+    env.set_caller(OWNER);
+    revoke_role(&mut env, ROLE_DJ_MANAGER, manager).unwrap();
+    assert_eq!(get_role_members(&env, &ROLE_DJ_MANAGER), Ok(vec![]));
+    env.set_caller(manager);
+    assert_eq!(register_dj(&mut env, [5u8; 20]), Err(Error::MissingRole));
+    assert_eq!(env.events_with_topic(EVENT_ROLE_GRANTED).len(), 2);
+    assert_eq!(env.events_with_topic(EVENT_ROLE_REVOKED).len(), 1);
+}
+
+#[test]
+fn admins_hold_every_role() {
+    let mut env = setup();
+    grant_role(&mut env, ROLE_ADMIN, OTHER).unwrap();
+
+    env.set_caller(OTHER);
+    grant_role(&mut env, ROLE_MODERATOR, VOTER).unwrap();
+    register_dj(&mut env, VOTER).unwrap();
+    assert!(is_dj(&env, VOTER));
+}
+
+#[test]
+fn pages_report_the_total() {
+    let mut env = setup();
+    for dj in [[5u8; 20], [6u8; 20], [7u8; 20]] {
+        register_dj(&mut env, dj).unwrap();
+    }
+
+    assert_eq!(get_all_djs_page(&env, 1, 2), (vec![[5u8; 20], [6u8; 20]], 4));
+    assert_eq!(get_all_djs_page(&env, 3, 10), (vec![[7u8; 20]], 4));
+    assert_eq!(get_all_djs_page(&env, 9, 10), (vec![], 4));
+    assert_eq!(get_active_djs_page(&env, 0, 10), (vec![], 0));
+
+    let mut env = setup_live_set();
+    env.set_caller(DJ);
+    add_song(&mut env, b"Artist - Third".to_vec()).unwrap();
+    remove_song(&mut env, 1).unwrap();
+    let (songs, total) = get_songs_with_votes_page(&env, DJ, 1, 0, 2);
+    assert_eq!(total, 3);
+    assert_eq!(songs.iter().map(|s| s.0).collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn cursor_skips_removed_songs() {
+    let mut env = setup_live_set();
+    env.set_caller(DJ);
+    for name in [&b"Artist - Third"[..], b"Artist - Fourth", b"Artist - Fifth"] {
+        add_song(&mut env, name.to_vec()).unwrap();
+    }
+    remove_song(&mut env, 1).unwrap();
+    remove_song(&mut env, 2).unwrap();
+
+    let (page, cursor) = get_songs_with_votes_from(&env, DJ, 1, 0, 2);
+    assert_eq!(page.iter().map(|s| s.0).collect::<Vec<_>>(), vec![0, 3]);
+    assert_eq!(cursor, 4);
+    let (page, cursor) = get_songs_with_votes_from(&env, DJ, 1, cursor, 2);
+    assert_eq!(page.iter().map(|s| s.0).collect::<Vec<_>>(), vec![4]);
+    assert_eq!(cursor, 5);
+    assert_eq!(get_songs_with_votes_from(&env, DJ, 1, 42, 2), (vec![], 5));
+
+    // Through dispatch the page size is capped and completion is flagged
+    let output = call(&mut env, SELECTOR_GET_SONGS_WITH_VOTES_FROM, &[
+        Token::Address(DJ.into()),
+        Token::Uint(1.into()),
+        Token::Uint(0.into()),
+        Token::Uint(ethabi::Uint::MAX),
+    ]).unwrap();
+    let decoded = decode(&[
+        ParamType::Array(Box::new(ParamType::Tuple(vec![ParamType::Uint(256), ParamType::String, ParamType::Uint(256)]))),
+        ParamType::Uint(256),
+        ParamType::Bool,
+    ], &output).unwrap();
+    assert_eq!(decoded[0].clone().into_array().unwrap().len(), 3);
+    assert_eq!(decoded[2], Token::Bool(false));
+}
+
+#[test]
+fn strings_round_trip_up_to_their_limit() {
+    let mut env = setup();
+    env.set_caller(DJ);
+
+    // Metadata past 256 bytes and values ending in NUL survive the round trip
+    let metadata = vec![b'x'; MAX_METADATA_LEN];
+    set_dj_metadata(&mut env, DJ, metadata.clone()).unwrap();
+    assert_eq!(get_dj_metadata(&env, DJ), metadata);
+    set_dj_metadata(&mut env, DJ, b"name\0\0".to_vec()).unwrap();
+    assert_eq!(get_dj_metadata(&env, DJ), b"name\0\0".to_vec());
+    set_dj_metadata(&mut env, DJ, Vec::new()).unwrap();
+    assert_eq!(get_dj_metadata(&env, DJ), Vec::<u8>::new());
+
+    let name = vec![b'y'; MAX_SONG_NAME_LEN];
+    let song_id = add_song(&mut env, name.clone()).unwrap();
+    assert_eq!(get_song(&env, DJ, 0, song_id), name);
+}
+
+#[test]
+fn legacy_strings_migrate_in_batches() {
+    let mut env = setup();
+    let metadata = vec![b'm'; 300];
+    env.set_storage(&get_dj_metadata_key(&DJ), &metadata);
+    for i in 0..3u32 {
+        env.set_storage(&get_song_key(&DJ, 0, i), format!("Legacy {}", i).as_bytes());
+    }
+    env.set_storage(&get_song_count_key(&DJ, 0), &3u32.to_le_bytes());
+
+    // Headerless values stay readable before migration
+    assert_eq!(get_dj_metadata(&env, DJ), metadata);
+    assert_eq!(get_song(&env, DJ, 0, 2), b"Legacy 2".to_vec());
+
+    // Rewritten by the v1 -> v2 step of `migrate`
+    env.storage.remove(&get_schema_version_key());
+    // The metadata and each song name count against the batch
+    assert_eq!(migrate(&mut env, 2), Ok((1, false)));
+    assert_eq!(env.storage[&get_song_key(&DJ, 0, 0)][..2], [STRING_MARKER, STRING_ENCODING_V1]);
+    assert_eq!(env.storage[&get_song_key(&DJ, 0, 1)], b"Legacy 1".to_vec());
+    // Two songs, then the step past the DJ's last set
+    assert_eq!(migrate(&mut env, 3), Ok((2, true)));
+    assert_eq!(env.storage[&get_song_key(&DJ, 0, 2)][0], STRING_MARKER);
+
+    assert_eq!(get_dj_metadata(&env, DJ), metadata);
+    for i in 0..3u32 {
+        assert_eq!(get_song(&env, DJ, 0, i), format!("Legacy {}", i).into_bytes());
+    }
+}
+
+#[test]
+fn migrate_upgrades_a_legacy_deployment_in_batches() {
+    let mut env = setup();
+    assert_eq!(get_schema_version(&env), CURRENT_SCHEMA_VERSION);
+    assert_eq!(migrate(&mut env, 10), Ok((CURRENT_SCHEMA_VERSION, true)));
+
+    // Rewind to a deployment from before the version was stored
+    env.storage.remove(&get_schema_version_key());
+    assert_eq!(get_schema_version(&env), 1);
+    register_dj(&mut env, OTHER).unwrap();
+    env.set_storage(&get_dj_metadata_key(&DJ), b"Legacy DJ");
+    for (dj, set_id, count) in [(DJ, 0u32, 2u32), (DJ, 1, 1), (OTHER, 0, 1)] {
+        for i in 0..count {
+            env.set_storage(&get_song_key(&dj, set_id, i), b"Legacy track");
+        }
+        env.set_storage(&get_song_count_key(&dj, set_id), &count.to_le_bytes());
+    }
+    env.set_storage(&get_current_set_key(&DJ), &1u32.to_le_bytes());
+
+    env.set_caller(DJ);
+    assert_eq!(migrate(&mut env, 2), Err(Error::NotOwner));
+    env.set_caller(OWNER);
+    assert_eq!(migrate(&mut env, 0), Err(Error::InvalidParams));
+    assert_eq!(migrate(&mut env, 2), Ok((1, false)));
+    assert_eq!(env.storage[&get_song_key(&DJ, 1, 0)][0], b'L');
+    assert_eq!(env.events_with_topic(EVENT_SCHEMA_MIGRATED).len(), 0);
+    // DJ's last song and two set steps, then OTHER's metadata, song and set step
+    assert_eq!(migrate(&mut env, 6), Ok((1, false)));
+    assert_eq!(migrate(&mut env, 1), Ok((2, true)));
+    assert_eq!(get_schema_version(&env), 2);
+    assert_eq!(env.events_with_topic(EVENT_SCHEMA_MIGRATED).len(), 1);
+
+    for key in [get_dj_metadata_key(&DJ), get_song_key(&DJ, 0, 1), get_song_key(&DJ, 1, 0), get_song_key(&OTHER, 0, 0)] {
+        assert_eq!(env.storage[&key][0], STRING_MARKER);
+    }
+    assert_eq!(get_song(&env, DJ, 1, 0), b"Legacy track".to_vec());
+}
+
+#[test]
+fn upgrade_keeps_storage_and_records_the_previous_code() {
+    let mut env = setup_live_set();
+    let original = [0xaa; 32];
+    let fixed = [0xbb; 32];
+    env.code_hash = original;
+
+    env.set_caller(DJ);
+    assert_eq!(upgrade_to(&mut env, fixed, false), Err(Error::NotOwner));
+
+    // A live set blocks the upgrade unless it is forced
+    env.set_caller(OWNER);
+    assert_eq!(upgrade_to(&mut env, fixed, false), Err(Error::SetsActive));
+    assert_eq!(upgrade_to(&mut env, original, true), Err(Error::InvalidParams));
+    upgrade_to(&mut env, fixed, true).unwrap();
+    assert_eq!(env.code_hash, fixed);
+    assert_eq!(get_previous_code_hash(&env), original);
+    assert_eq!(get_song(&env, DJ, 1, 0), b"Artist - First".to_vec());
+
+    // Rolling back is an upgrade to the recorded hash
+    stop_set(&mut env, DJ).unwrap();
+    let rollback = get_previous_code_hash(&env);
+    upgrade_to(&mut env, rollback, false).unwrap();
+    assert_eq!(env.code_hash, original);
+    assert_eq!(get_previous_code_hash(&env), fixed);
+    assert_eq!(env.events_with_topic(EVENT_UPGRADED).len(), 2);
+}
+
+#[test]
+fn detailed_songs_keep_the_string_api_working() {
+    let mut env = setup_live_set();
+    env.set_caller(DJ);
+    env.set_now(1_200);
+    let song_id = add_song_detailed(&mut env, b"Title".to_vec(), b"Artist".to_vec(), b"spotify:track:abc".to_vec(), 215).unwrap();
+    assert_eq!(get_song(&env, DJ, 1, song_id), b"Artist - Title".to_vec());
+    assert_eq!(
+        get_song_details(&env, DJ, 1, song_id),
+        Ok((b"Title".to_vec(), b"Artist".to_vec(), b"spotify:track:abc".to_vec(), 215, DJ, 1_200))
+    );
+
+    env.set_caller(VOTER);
+    let suggested = suggest_song_detailed(&mut env, DJ, b"Untitled".to_vec(), Vec::new(), Vec::new(), 0).unwrap();
+    assert_eq!(get_song(&env, DJ, 1, suggested), b"Untitled".to_vec());
+    vote(&mut env, DJ, suggested).unwrap();
+
+    // String API songs report their name as the title
+    assert_eq!(get_song_details(&env, DJ, 1, 0), Ok((b"Artist - First".to_vec(), Vec::new(), Vec::new(), 0, DJ, 1_000)));
+    assert_eq!(get_song_details(&env, DJ, 1, 9), Err(Error::SongNotFound));
+
+    assert_eq!(suggest_song_detailed(&mut env, DJ, Vec::new(), b"Artist".to_vec(), Vec::new(), 0), Err(Error::EmptySongName));
+    assert_eq!(suggest_song_detailed(&mut env, DJ, vec![b't'; MAX_SONG_TITLE_LEN + 1], Vec::new(), Vec::new(), 0), Err(Error::SongNameTooLong));
+    assert_eq!(suggest_song_detailed(&mut env, DJ, b"T".to_vec(), Vec::new(), vec![b'u'; MAX_SOURCE_URI_LEN + 1], 0), Err(Error::SourceUriTooLong));
+    env.set_caller(DJ);
+    let longest = add_song_detailed(&mut env, vec![b't'; MAX_SONG_TITLE_LEN], vec![b'a'; MAX_SONG_ARTIST_LEN], Vec::new(), 0).unwrap();
+    assert_eq!(get_song(&env, DJ, 1, longest).len(), MAX_SONG_NAME_LEN);
+}
+
+#[test]
+fn suggestions_are_credited_to_the_suggester() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    env.set_now(1_100);
+    let first = suggest_song(&mut env, DJ, b"Request - One".to_vec()).unwrap();
+    env.set_caller(OTHER);
+    suggest_song(&mut env, DJ, b"Request - Two".to_vec()).unwrap();
+    env.set_caller(VOTER);
+    env.set_now(1_300);
+    let third = suggest_song(&mut env, DJ, b"Request - Three".to_vec()).unwrap();
+
+    // The DJ's own entries are told apart from audience requests
+    assert_eq!(get_song_origin(&env, DJ, 1, 0), Ok((DJ, 1_000, false)));
+    assert_eq!(get_song_origin(&env, DJ, 1, first), Ok((VOTER, 1_100, true)));
+    assert_eq!(get_song_origin(&env, DJ, 1, third), Ok((VOTER, 1_300, true)));
+    assert_eq!(get_song_origin(&env, DJ, 1, 42), Err(Error::SongNotFound));
+
+    assert_eq!(get_songs_by_suggester(&env, DJ, 1, VOTER), vec![first, third]);
+    assert_eq!(get_songs_by_suggester(&env, DJ, 1, DJ), vec![]);
+    assert_eq!(get_songs_by_suggester(&env, DJ, 0, VOTER), vec![]);
+}
+
+#[test]
+fn pending_suggestions_wait_for_review() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    assert_eq!(set_suggestion_approval(&mut env, true), Err(Error::NotDj));
+    env.set_caller(DJ);
+    set_suggestion_approval(&mut env, true).unwrap();
+    assert!(requires_suggestion_approval(&env, DJ));
+    add_delegate(&mut env, OTHER, PERMISSION_APPROVE_SUGGESTION).unwrap();
+
+    // DJ entries skip review; suggestions wait outside the queue
+    let own = add_song(&mut env, b"Artist - Own".to_vec()).unwrap();
+    env.set_caller(VOTER);
+    let troll = suggest_song(&mut env, DJ, b"Troll - Entry".to_vec()).unwrap();
+    let good = suggest_song(&mut env, DJ, b"Crowd - Favourite".to_vec()).unwrap();
+    assert_eq!(vote(&mut env, DJ, good), Err(Error::SongPending));
+    vote(&mut env, DJ, own).unwrap();
+    assert_eq!(get_pending_suggestions(&env, DJ, 1).iter().map(|s| s.0).collect::<Vec<_>>(), vec![troll, good]);
+    assert_eq!(get_all_songs_with_votes(&env, DJ, 1).len(), 3);
+    assert_eq!(get_queue_position(&env, DJ, 1, good), Err(Error::SongPending));
+
+    assert_eq!(review_suggestion(&mut env, DJ, good, true), Err(Error::Unauthorized));
+    env.set_caller(OTHER);
+    review_suggestion(&mut env, DJ, good, true).unwrap();
+    review_suggestion(&mut env, DJ, troll, false).unwrap();
+    assert_eq!(review_suggestion(&mut env, DJ, good, true), Err(Error::NotPending));
+    assert_eq!(review_suggestion(&mut env, DJ, own, false), Err(Error::NotPending));
+
+    assert!(get_pending_suggestions(&env, DJ, 1).is_empty());
+    assert!(is_song_removed(&env, DJ, 1, troll));
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, good).unwrap();
+    assert_eq!(get_all_songs_with_votes(&env, DJ, 1).len(), 4);
+    assert_eq!(env.events_with_topic(EVENT_SUGGESTION_APPROVED).len(), 1);
+    assert_eq!(env.events_with_topic(EVENT_SUGGESTION_REJECTED).len(), 1);
+}
+
+#[test]
+fn suggestions_are_throttled_per_address() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    assert_eq!(set_suggestion_limits(&mut env, 2, 60), Err(Error::NotDj));
+    assert_eq!(get_suggestion_allowance(&env, DJ, 1, VOTER), (None, 0));
+
+    env.set_caller(DJ);
+    set_suggestion_limits(&mut env, 2, 60).unwrap();
+    assert_eq!(get_suggestion_limits(&env, DJ), (2, 60));
+
+    env.set_caller(VOTER);
+    env.set_now(1_000);
+    suggest_song(&mut env, DJ, b"Request - One".to_vec()).unwrap();
+    assert_eq!(get_suggestion_allowance(&env, DJ, 1, VOTER), (Some(1), 1_060));
+    env.set_now(1_059);
+    assert_eq!(suggest_song(&mut env, DJ, b"Request - Two".to_vec()), Err(Error::SuggestionTooSoon));
+
+    // Other addresses are throttled independently
+    env.set_caller(OTHER);
+    suggest_song(&mut env, DJ, b"Request - Other".to_vec()).unwrap();
+
+    env.set_caller(VOTER);
+    env.set_now(1_060);
+    suggest_song(&mut env, DJ, b"Request - Two".to_vec()).unwrap();
+    env.set_now(5_000);
+    assert_eq!(suggest_song(&mut env, DJ, b"Request - Three".to_vec()), Err(Error::SuggestionLimitReached));
+    assert_eq!(get_suggestion_allowance(&env, DJ, 1, VOTER), (Some(0), 1_120));
+
+    // Suggesting a song already in the set resolves to it without touching the allowance
+    assert_eq!(suggest_song(&mut env, DJ, b"Artist - First".to_vec()), Ok(0));
+    assert_eq!(get_suggestion_allowance(&env, DJ, 1, VOTER), (Some(0), 1_120));
+
+    // The count starts over with the next set
+    env.set_caller(DJ);
+    stop_set(&mut env, DJ).unwrap();
+    start_set(&mut env, DJ).unwrap();
+    env.set_caller(VOTER);
+    suggest_song(&mut env, DJ, b"Request - Three".to_vec()).unwrap();
+}
+
+#[test]
+fn duplicates_resolve_to_the_existing_song() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    let spelled_differently = suggest_song(&mut env, DJ, b"  artist -   FIRST ".to_vec()).unwrap();
+    assert_eq!(spelled_differently, 0);
+    assert_eq!(get_song_count(&env, DJ, 1), 2);
+    assert!(get_songs_by_suggester(&env, DJ, 1, VOTER).is_empty());
+    assert_eq!(find_song(&env, DJ, b"ARTIST - second", b""), Some(1));
+
+    // With a source URI the URI identifies the track, whatever the spelling
+    let by_uri = suggest_song_detailed(&mut env, DJ, b"Track".to_vec(), Vec::new(), b"spotify:track:1".to_vec(), 0).unwrap();
+    let again = suggest_song_detailed(&mut env, DJ, b"Other spelling".to_vec(), Vec::new(), b"spotify:track:1".to_vec(), 0).unwrap();
+    assert_eq!(again, by_uri);
+    assert_eq!(get_song_details(&env, DJ, 1, by_uri).unwrap().0, b"Track".to_vec());
+
+    env.set_caller(DJ);
+    set_reject_duplicates(&mut env, true).unwrap();
+    assert_eq!(add_song(&mut env, b"Artist - Second".to_vec()), Err(Error::DuplicateSong));
+    env.set_caller(VOTER);
+    assert_eq!(suggest_song(&mut env, DJ, b"artist - second".to_vec()), Err(Error::DuplicateSong));
+
+    // Removed or played songs no longer count as duplicates
+    env.set_caller(DJ);
+    remove_song(&mut env, 1).unwrap();
+    assert_eq!(add_song(&mut env, b"Artist - Second".to_vec()), Ok(3));
+    advance(&mut env, DJ).unwrap();
+    assert_eq!(find_song(&env, DJ, b"Artist - First", b""), None);
+    assert_eq!(add_song(&mut env, b"Artist - First".to_vec()), Ok(4));
+}
+
+#[test]
+fn merge_moves_votes_and_counts_double_voters_once() {
+    let mut env = setup_live_set();
+    env.set_caller(DJ);
+    // Make the entries distinct by source so they are not caught as duplicates
+    let duplicate = add_song_detailed(&mut env, b"First".to_vec(), b"Artist".to_vec(), b"spotify:track:dup".to_vec(), 0).unwrap();
+
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+    vote(&mut env, DJ, duplicate).unwrap();
+    env.set_caller(OTHER);
+    vote(&mut env, DJ, duplicate).unwrap();
+    unvote(&mut env, DJ, duplicate).unwrap();
+    vote(&mut env, DJ, duplicate).unwrap();
+    assert_eq!(get_song_voters(&env, DJ, 1, duplicate), vec![VOTER, OTHER]);
+
+    assert_eq!(merge_songs(&mut env, DJ, duplicate, 0), Err(Error::Unauthorized));
+    env.set_caller(DJ);
+    assert_eq!(merge_songs(&mut env, DJ, 0, 0), Err(Error::InvalidParams));
+    assert_eq!(merge_songs(&mut env, DJ, duplicate, 0), Ok(2));
+
+    assert_eq!(get_votes(&env, DJ, 1, 0), 2);
+    assert_eq!(get_votes(&env, DJ, 1, duplicate), 0);
+    assert!(has_voted(&env, OTHER, DJ, 1, 0));
+    assert!(!has_voted(&env, OTHER, DJ, 1, duplicate));
+    assert_eq!(get_merged_into(&env, DJ, 1, duplicate), Some(0));
+    assert_eq!(get_merged_into(&env, DJ, 1, 0), None);
+    assert_eq!(merge_songs(&mut env, DJ, duplicate, 1), Err(Error::SongWasMerged));
+
+    // The source leaves the queue, and its key now resolves to the target
+    assert!(get_all_songs_with_votes(&env, DJ, 1).iter().all(|s| s.0 != duplicate));
+    env.set_caller(VOTER);
+    assert_eq!(vote(&mut env, DJ, duplicate), Err(Error::SongWasMerged));
+    assert_eq!(suggest_song_detailed(&mut env, DJ, b"x".to_vec(), Vec::new(), b"spotify:track:dup".to_vec(), 0), Ok(0));
+    unvote(&mut env, DJ, 0).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 0), 1);
+}
+
+#[test]
+fn votes_spend_from_a_per_set_budget() {
+    let mut env = setup_live_set();
+    env.set_caller(DJ);
+    add_song(&mut env, b"Artist - Third".to_vec()).unwrap();
+    assert_eq!(get_remaining_votes(&env, VOTER, DJ, 1), None);
+    set_vote_budget(&mut env, 2).unwrap();
+
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+    vote(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_remaining_votes(&env, VOTER, DJ, 1), Some(0));
+    assert_eq!(vote(&mut env, DJ, 2), Err(Error::VoteBudgetExceeded));
+
+    // Unvoting refunds, and other voters have their own budget
+    unvote(&mut env, DJ, 0).unwrap();
+    assert_eq!(get_remaining_votes(&env, VOTER, DJ, 1), Some(1));
+    vote(&mut env, DJ, 2).unwrap();
+    assert_eq!(get_remaining_votes(&env, OTHER, DJ, 1), Some(2));
+
+    // A voter who backed both merged songs gets the collapsed vote back
+    env.set_caller(DJ);
+    merge_songs(&mut env, DJ, 2, 1).unwrap();
+    assert_eq!(get_remaining_votes(&env, VOTER, DJ, 1), Some(1));
+
+    let output = call(&mut env, SELECTOR_GET_REMAINING_VOTES, &[Token::Address(VOTER.into()), Token::Address(DJ.into())]).unwrap();
+    assert_eq!(decode(&[ParamType::Uint(256)], &output).unwrap(), vec![Token::Uint(1.into())]);
+}
+
+#[test]
+fn downvotes_push_songs_down_and_can_hide_them() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+    assert_eq!(downvote(&mut env, DJ, 0), Err(Error::ConflictingVote));
+    downvote(&mut env, DJ, 1).unwrap();
+    assert_eq!(downvote(&mut env, DJ, 1), Err(Error::AlreadyVoted));
+    assert_eq!(vote(&mut env, DJ, 1), Err(Error::ConflictingVote));
+    env.set_caller(OTHER);
+    downvote(&mut env, DJ, 0).unwrap();
+    downvote(&mut env, DJ, 1).unwrap();
+
+    // Net score drives the ranking; the plain vote views still report upvotes
+    assert_eq!(get_songs_with_scores(&env, DJ, 1), vec![
+        (0, b"Artist - First".to_vec(), 1, 1, 0, false),
+        (1, b"Artist - Second".to_vec(), 0, 2, -2, false),
+    ]);
+    assert_eq!(get_queue_position(&env, DJ, 1, 1), Ok(2));
+
+    env.set_caller(DJ);
+    assert_eq!(get_auto_hide(&env, DJ), (false, 0));
+    set_auto_hide(&mut env, true, -1).unwrap();
+    assert_eq!(get_all_songs_with_votes(&env, DJ, 1).iter().map(|s| s.0).collect::<Vec<_>>(), vec![0]);
+    assert!(get_songs_with_scores(&env, DJ, 1)[1].5);
+    assert_eq!(get_queue_position(&env, DJ, 1, 1), Err(Error::SongHidden));
+
+    // Lifting the score above the threshold brings the song back
+    env.set_caller(OTHER);
+    undownvote(&mut env, DJ, 1).unwrap();
+    assert_eq!(undownvote(&mut env, DJ, 1), Err(Error::NotVoted));
+    assert_eq!(get_all_songs_with_votes(&env, DJ, 1).len(), 2);
+    vote(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_net_score(&env, DJ, 1, 1), 0);
+    assert_eq!(env.events_with_topic(EVENT_DOWNVOTED).len(), 3);
+}
+
+#[test]
+fn int256_thresholds_round_trip() {
+    for value in [0i64, 5, -1, -300, i64::MIN, i64::MAX] {
+        assert_eq!(token_to_i64(&i64_to_token(value)), Ok(value));
+    }
+    assert_eq!(token_to_i64(&Token::Int(ethabi::Uint::MAX >> 1)), Err(Error::InvalidParams));
+    assert_eq!(i64_to_token(-1), Token::Int(ethabi::Uint::MAX));
+}
+
+#[test]
+fn quadratic_votes_cost_the_square_in_credits() {
+    let mut env = setup();
+    env.set_caller(DJ);
+    set_quadratic_voting(&mut env, true, 10).unwrap();
+    start_set(&mut env, DJ).unwrap();
+    add_song(&mut env, b"Artist - First".to_vec()).unwrap();
+    add_song(&mut env, b"Artist - Second".to_vec()).unwrap();
+    assert_eq!(set_quadratic_voting(&mut env, false, 0), Err(Error::SetAlreadyActive));
+
+    env.set_caller(VOTER);
+    assert_eq!(vote(&mut env, DJ, 0), Err(Error::WrongVotingMode));
+    assert_eq!(cast_votes(&mut env, DJ, 0, 2), Ok(2));
+    assert_eq!(get_remaining_credits(&env, VOTER, DJ, 1), 6);
+    // Going from 2 to 3 votes costs 9 - 4
+    assert_eq!(cast_votes(&mut env, DJ, 0, 1), Ok(3));
+    assert_eq!(get_remaining_credits(&env, VOTER, DJ, 1), 1);
+    assert_eq!(cast_votes(&mut env, DJ, 1, 2), Err(Error::InsufficientCredits));
+    cast_votes(&mut env, DJ, 1, 1).unwrap();
+    assert_eq!(get_top_songs(&env, DJ, 1, 1)[0].2, 3);
+
+    assert_eq!(withdraw_votes(&mut env, DJ, 0, 4), Err(Error::NotVoted));
+    assert_eq!(withdraw_votes(&mut env, DJ, 0, 1), Ok(2));
+    assert_eq!(get_remaining_credits(&env, VOTER, DJ, 1), 5);
+    assert_eq!(get_votes_cast(&env, VOTER, DJ, 1, 0), 2);
+
+    // A voter on both songs keeps their votes on the target and gets the source credits back
+    env.set_caller(DJ);
+    merge_songs(&mut env, DJ, 1, 0).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 0), 2);
+    assert_eq!(get_remaining_credits(&env, VOTER, DJ, 1), 6);
+    env.set_caller(VOTER);
+    assert_eq!(withdraw_votes(&mut env, DJ, 0, 2), Ok(0));
+    assert!(!has_voted(&env, VOTER, DJ, 1, 0));
+    assert_eq!(get_remaining_credits(&env, VOTER, DJ, 1), 10);
+}
+
+#[test]
+fn decayed_votes_let_newer_songs_overtake() {
+    let mut env = setup_live_set();
+    set_vote_decay(&mut env, 600).unwrap();
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+    env.set_caller(OTHER);
+    vote(&mut env, DJ, 0).unwrap();
+
+    // Two votes from ten minutes ago weigh one between them, so a fresh vote ties
+    env.set_now(1_600);
+    vote(&mut env, DJ, 1).unwrap();
+    let top = get_top_songs_with_scores(&env, DJ, 1, 2);
+    assert_eq!((top[0].0, top[0].2, top[0].4), (0, 2, 1_000_000));
+    assert_eq!((top[1].0, top[1].2, top[1].4), (1, 1, 1_000_000));
+
+    env.set_now(1_900);
+    assert_eq!(get_queue_position(&env, DJ, 1, 1), Ok(1));
+    assert_eq!(get_top_songs(&env, DJ, 1, 1), vec![(1, b"Artist - Second".to_vec(), 1)]);
+    assert_eq!(get_ranking_score(&env, DJ, 1, 1), 707_107);
+
+    // Without a half-life the raw counts rank again
+    env.set_caller(DJ);
+    set_vote_decay(&mut env, 0).unwrap();
+    assert_eq!(get_queue_position(&env, DJ, 1, 0), Ok(1));
+    assert_eq!(get_ranking_score(&env, DJ, 1, 0), 2_000_000);
+}
+
+#[test]
+fn decay_weight_halves_each_half_life() {
+    assert_eq!(decay_weight(0, 60), SCORE_SCALE);
+    assert_eq!(decay_weight(30, 60), 707_107);
+    assert_eq!(decay_weight(60, 60), 500_000);
+    assert_eq!(decay_weight(150, 60), 176_776);
+    assert_eq!(decay_weight(u64::MAX, 1), 0);
+}
+
+#[test]
+fn token_weighted_votes_use_the_balance_at_cast_time() {
+    const TOKEN: [u8; 20] = [9u8; 20];
+    let mut env = setup_live_set();
+    env.token(TOKEN).balances.insert(VOTER, 250.into());
+    env.token(TOKEN).balances.insert(OTHER, 1_600.into());
+    set_vote_token(&mut env, TOKEN, WEIGHT_CURVE_LINEAR, 10.into(), 0).unwrap();
+    assert_eq!(set_vote_token(&mut env, TOKEN, 3, 10.into(), 0), Err(Error::InvalidParams));
+
+    env.set_caller(VOTER);
+    assert_eq!(get_vote_weight(&env, VOTER, DJ), Ok(25));
+    vote(&mut env, DJ, 0).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 0), 25);
+
+    env.set_caller(DJ);
+    set_vote_token(&mut env, TOKEN, WEIGHT_CURVE_SQRT, 1.into(), 0).unwrap();
+    env.set_caller(OTHER);
+    vote(&mut env, DJ, 0).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 0), 65);
+
+    // Unvoting takes back the recorded weight even after the curve and balance changed
+    env.token(TOKEN).balances.insert(VOTER, 0.into());
+    env.set_caller(VOTER);
+    unvote(&mut env, DJ, 0).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 0), 40);
+    assert_eq!(vote(&mut env, DJ, 1), Err(Error::NoVotingWeight));
+    assert_eq!(downvote(&mut env, DJ, 1), Err(Error::NoVotingWeight));
+
+    // Downvotes carry the same weight and take it back the same way
+    env.set_caller(OTHER);
+    downvote(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_downvotes(&env, DJ, 1, 1), 40);
+    assert_eq!(get_net_score(&env, DJ, 1, 1), -40);
+
+    env.set_caller(DJ);
+    // A zero cap would leave every voter without weight
+    assert_eq!(set_vote_token(&mut env, TOKEN, WEIGHT_CURVE_CAPPED, 100.into(), 0), Err(Error::InvalidParams));
+    set_vote_token(&mut env, TOKEN, WEIGHT_CURVE_CAPPED, 100.into(), 3).unwrap();
+    assert_eq!(get_vote_weight(&env, OTHER, DJ), Ok(3));
+    env.set_caller(OTHER);
+    undownvote(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_downvotes(&env, DJ, 1, 1), 0);
+    env.set_caller(DJ);
+    set_vote_token(&mut env, [8u8; 20], WEIGHT_CURVE_LINEAR, 1.into(), 0).unwrap();
+    assert_eq!(get_vote_weight(&env, OTHER, DJ), Err(Error::TokenCallFailed));
+    set_vote_token(&mut env, [0u8; 20], WEIGHT_CURVE_LINEAR, 0.into(), 0).unwrap();
+    assert_eq!(get_vote_token(&env, DJ).0, None);
+    assert_eq!(get_vote_weight(&env, VOTER, DJ), Ok(1));
+}
+
+#[test]
+fn ticket_gate_limits_votes_and_suggestions_to_holders() {
+    const TICKETS: [u8; 20] = [9u8; 20];
+    let mut env = setup_live_set();
+    env.token(TICKETS).balances.insert(VOTER, 1.into());
+    assert_eq!(set_gate(&mut env, GATE_ERC721, [0u8; 20], 0.into()), Err(Error::InvalidParams));
+    set_gate(&mut env, GATE_ERC721, TICKETS, 0.into()).unwrap();
+    assert_eq!(get_gate(&env, DJ), (GATE_ERC721, TICKETS, 0.into()));
+
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+    suggest_song(&mut env, DJ, b"Artist - Third".to_vec()).unwrap();
+    env.set_caller(OTHER);
+    assert_eq!(vote(&mut env, DJ, 0), Err(Error::NotTicketHolder));
+    assert_eq!(suggest_song(&mut env, DJ, b"Artist - Fourth".to_vec()), Err(Error::NotTicketHolder));
+
+    // An ERC-1155 gate only accepts the configured token ID
+    env.token(TICKETS).id_balances.insert((OTHER, 7.into()), 2.into());
+    env.set_caller(DJ);
+    set_gate(&mut env, GATE_ERC1155, TICKETS, 7.into()).unwrap();
+    env.set_caller(VOTER);
+    assert_eq!(unvote(&mut env, DJ, 0), Err(Error::NotTicketHolder));
+    env.set_caller(OTHER);
+    vote(&mut env, DJ, 1).unwrap();
+
+    env.set_caller(DJ);
+    set_gate(&mut env, GATE_NONE, [0u8; 20], 0.into()).unwrap();
+    env.set_caller(VOTER);
+    unvote(&mut env, DJ, 0).unwrap();
+}
+
+#[test]
+fn queue_built_off_air_opens_with_the_next_set() {
+    let mut env = setup();
+    env.set_caller(DJ);
+    add_song(&mut env, b"Artist - Opener".to_vec()).unwrap();
+    add_song(&mut env, b"Artist - Closer".to_vec()).unwrap();
+    start_set(&mut env, DJ).unwrap();
+    assert_eq!(get_current_set_id(&env, DJ), 1);
+
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_top_songs(&env, DJ, 1, 1), vec![(1, b"Artist - Closer".to_vec(), 1)]);
+
+    // Songs added after the set stops move to the next set and leave the finished one as it was
+    env.set_caller(DJ);
+    stop_set(&mut env, DJ).unwrap();
+    assert_eq!(add_song(&mut env, b"Artist - Encore".to_vec()), Ok(2));
+    start_set(&mut env, DJ).unwrap();
+    assert_eq!(get_song_count(&env, DJ, 1), 2);
+    assert_eq!(get_song_count(&env, DJ, 2), 1);
+    assert_eq!(get_song(&env, DJ, 2, 0), b"Artist - Encore".to_vec());
+    assert_eq!(find_song(&env, DJ, b"artist - encore", &[]), Some(0));
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+}
+
+#[test]
+fn read_views_agree_after_a_set_stops() {
+    let mut env = setup_live_set();
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 1).unwrap();
+    env.set_caller(DJ);
+    stop_set(&mut env, DJ).unwrap();
+
+    // Every view keeps reporting the finished set until the next one starts
+    let params = [Token::Address(DJ.into()), Token::Uint(1u32.into())];
+    assert_eq!(call(&mut env, SELECTOR_GET_SONG, &params), Ok(encode(&[Token::String("Artist - Second".into())])));
+    assert_eq!(call(&mut env, SELECTOR_GET_VOTES, &params), Ok(encode(&[Token::Uint(1u32.into())])));
+    assert_eq!(
+        call(&mut env, SELECTOR_GET_SONG_COUNT, &[Token::Address(DJ.into())]),
+        Ok(encode(&[Token::Uint(2u32.into())]))
+    );
+}
+
+#[test]
+fn clearing_votes_drops_both_kinds_but_keeps_a_same_block_revote() {
+    let mut env = setup_live_set();
+    set_vote_decay(&mut env, 600).unwrap();
+    env.set_caller(VOTER);
+    vote(&mut env, DJ, 0).unwrap();
+    env.set_caller(OTHER);
+    downvote(&mut env, DJ, 1).unwrap();
+
+    env.set_now(1_500);
+    env.set_caller(OWNER);
+    clear_votes(&mut env, DJ, 0).unwrap();
+    clear_votes(&mut env, DJ, 1).unwrap();
+    assert_eq!(get_ranking_score(&env, DJ, 1, 0), 0);
+    assert_eq!(get_ranking_score(&env, DJ, 1, 1), 0);
+    assert_eq!(get_net_score(&env, DJ, 1, 1), 0);
+
+    env.set_caller(VOTER);
+    unvote(&mut env, DJ, 0).unwrap();
+    vote(&mut env, DJ, 0).unwrap();
+    assert_eq!(get_votes(&env, DJ, 1, 0), 1);
+    assert_eq!(get_ranking_score(&env, DJ, 1, 0), 1_000_000);
+}
+
+#[test]
+fn quadratic_votes_near_the_credit_limit_do_not_overflow() {
+    let mut env = setup();
+    env.set_caller(DJ);
+    set_quadratic_voting(&mut env, true, u32::MAX).unwrap();
+    start_set(&mut env, DJ).unwrap();
+    add_song(&mut env, b"Artist - First".to_vec()).unwrap();
+
+    env.set_caller(VOTER);
+    assert_eq!(cast_votes(&mut env, DJ, 0, u32::MAX), Err(Error::InsufficientCredits));
+    assert_eq!(cast_votes(&mut env, DJ, 0, 65_535), Ok(65_535));
+    assert_eq!(get_remaining_credits(&env, VOTER, DJ, 1), 131_070);
+    assert_eq!(cast_votes(&mut env, DJ, 0, 1), Err(Error::InsufficientCredits));
+    env.set_caller(OTHER);
+    assert_eq!(cast_votes(&mut env, DJ, 0, 65_535), Ok(131_070));
+}
+
+#[test]
+fn pending_suggestions_cannot_be_merged() {
+    let mut env = setup_live_set();
+    set_suggestion_approval(&mut env, true).unwrap();
+    env.set_caller(VOTER);
+    let pending = suggest_song(&mut env, DJ, b"Artist - First (Live)".to_vec()).unwrap();
+
+    env.set_caller(DJ);
+    assert_eq!(merge_songs(&mut env, DJ, pending, 0), Err(Error::SongPending));
+    assert_eq!(merge_songs(&mut env, DJ, 0, pending), Err(Error::SongPending));
+    review_suggestion(&mut env, DJ, pending, true).unwrap();
+    assert_eq!(merge_songs(&mut env, DJ, pending, 0), Ok(0));
+}